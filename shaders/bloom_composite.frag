@@ -0,0 +1,27 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+uniform sampler2D bloom_texture;
+
+const float INTENSITY = 0.6;
+const int FIRST_LEVEL = 1;
+const int LEVELS = 5;
+
+void main() {
+    //Each mip level is a wider blur of the bright pass, smoothed further with a four tap tent
+    vec3 bloom = vec3(0.0);
+    for (int level = FIRST_LEVEL; level < FIRST_LEVEL + LEVELS; level++) {
+        vec2 texel_size = 1.0 / textureSize(bloom_texture, level);
+        bloom += textureLod(bloom_texture, f_uvs + vec2(-1.0, -1.0) * texel_size, level).xyz;
+        bloom += textureLod(bloom_texture, f_uvs + vec2(1.0, -1.0) * texel_size, level).xyz;
+        bloom += textureLod(bloom_texture, f_uvs + vec2(-1.0, 1.0) * texel_size, level).xyz;
+        bloom += textureLod(bloom_texture, f_uvs + vec2(1.0, 1.0) * texel_size, level).xyz;
+    }
+    bloom /= float(LEVELS * 4);
+
+    frag_color = vec4(texture(image_texture, f_uvs).xyz + bloom * INTENSITY, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,20 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+
+//Light brighter than this starts to bloom, with a soft knee so the cutoff isn't visible
+const float THRESHOLD = 1.0;
+const float KNEE = 0.5;
+
+void main() {
+    vec3 color = texture(image_texture, f_uvs).xyz;
+    float brightness = max(color.r, max(color.g, color.b));
+    float soft = clamp(brightness - THRESHOLD + KNEE, 0.0, 2.0 * KNEE);
+    soft = soft * soft / (4.0 * KNEE + 0.0001);
+    float contribution = max(soft, brightness - THRESHOLD) / max(brightness, 0.0001);
+    frag_color = vec4(color * contribution, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,19 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+
+//How far apart the red and blue channels are at the corners of the screen, in uv units
+const float STRENGTH = 0.004;
+
+void main() {
+    //Fringes grow towards the edges like they do with a real lens
+    vec2 offset = (f_uvs - 0.5) * STRENGTH;
+    float r = texture(image_texture, f_uvs + offset).r;
+    float g = texture(image_texture, f_uvs).g;
+    float b = texture(image_texture, f_uvs - offset).b;
+    frag_color = vec4(r, g, b, 1.0);
+}
\ No newline at end of file
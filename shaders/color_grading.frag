@@ -0,0 +1,29 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+uniform sampler2D lut_texture;
+
+//The LUT is a strip of LUT_SIZE slices, one per blue value, with red across each slice and green going up
+const float LUT_SIZE = 16.0;
+
+vec2 lut_uv(vec3 color, float slice) {
+    //Inset by half a texel so that filtering never bleeds into the neighbouring slice
+    float u = (slice * LUT_SIZE + color.r * (LUT_SIZE - 1.0) + 0.5) / (LUT_SIZE * LUT_SIZE);
+    float v = (color.g * (LUT_SIZE - 1.0) + 0.5) / LUT_SIZE;
+    return vec2(u, v);
+}
+
+void main() {
+    vec3 color = clamp(texture(image_texture, f_uvs).xyz, 0.0, 1.0);
+
+    //Blend between the two slices on either side of this blue value
+    float blue = color.b * (LUT_SIZE - 1.0);
+    float slice = floor(blue);
+    vec3 lower = texture(lut_texture, lut_uv(color, slice)).xyz;
+    vec3 upper = texture(lut_texture, lut_uv(color, min(slice + 1.0, LUT_SIZE - 1.0))).xyz;
+    frag_color = vec4(mix(lower, upper, blue - slice), 1.0);
+}
\ No newline at end of file
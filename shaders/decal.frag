@@ -0,0 +1,17 @@
+#version 330 core
+
+in vec2 f_uvs;
+in vec4 f_color;
+
+out vec4 frag_color;
+
+uniform float ring_width;           //Zero for a filled ellipse, otherwise the width of a ring as a fraction of its radius
+
+void main() {
+    //Soft-edged ellipse filling the quad, darkest in the middle
+    //Rings are darkest halfway across their width instead
+    float d = length(f_uvs * 2.0 - 1.0);
+    float edge = ring_width > 0.0 ? abs(d - (1.0 - ring_width)) / ring_width : d;
+    float coverage = 1.0 - smoothstep(0.4, 1.0, edge);
+    frag_color = vec4(f_color.rgb, f_color.a * coverage);
+}
\ No newline at end of file
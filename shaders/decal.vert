@@ -0,0 +1,17 @@
+#version 330 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in vec2 uv;
+layout (location = 2) in mat4 model_matrix;
+layout (location = 6) in vec4 color;
+
+out vec2 f_uvs;
+out vec4 f_color;
+
+uniform mat4 view_projection;
+
+void main() {
+    f_uvs = uv;
+    f_color = color;
+    gl_Position = view_projection * model_matrix * vec4(position, 1.0);
+}
\ No newline at end of file
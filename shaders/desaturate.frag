@@ -0,0 +1,14 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+uniform vec4 tint;          //White for monochrome
+
+void main() {
+    vec3 color = texture(image_texture, f_uvs).xyz;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    frag_color = vec4(clamp(luminance * tint.xyz, 0.0, 1.0), 1.0);
+}
\ No newline at end of file
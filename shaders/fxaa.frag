@@ -0,0 +1,47 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+
+//Lottes' FXAA, in the simplified form that blurs along the local edge direction
+const float SPAN_MAX = 8.0;
+const float REDUCE_MUL = 1.0 / 8.0;
+const float REDUCE_MIN = 1.0 / 128.0;
+const vec3 LUMA = vec3(0.299, 0.587, 0.114);
+
+void main() {
+    vec2 texel_size = 1.0 / textureSize(image_texture, 0);
+    vec3 rgb_nw = texture(image_texture, f_uvs + vec2(-1.0, -1.0) * texel_size).xyz;
+    vec3 rgb_ne = texture(image_texture, f_uvs + vec2(1.0, -1.0) * texel_size).xyz;
+    vec3 rgb_sw = texture(image_texture, f_uvs + vec2(-1.0, 1.0) * texel_size).xyz;
+    vec3 rgb_se = texture(image_texture, f_uvs + vec2(1.0, 1.0) * texel_size).xyz;
+    vec3 rgb_m = texture(image_texture, f_uvs).xyz;
+
+    float luma_nw = dot(rgb_nw, LUMA);
+    float luma_ne = dot(rgb_ne, LUMA);
+    float luma_sw = dot(rgb_sw, LUMA);
+    float luma_se = dot(rgb_se, LUMA);
+    float luma_m = dot(rgb_m, LUMA);
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    //The blur runs perpendicular to the luma gradient
+    vec2 direction = vec2(-((luma_nw + luma_ne) - (luma_sw + luma_se)), (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    float inverse_direction_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_direction_min, -SPAN_MAX, SPAN_MAX) * texel_size;
+
+    vec3 rgb_a = 0.5 * (texture(image_texture, f_uvs + direction * (1.0 / 3.0 - 0.5)).xyz + texture(image_texture, f_uvs + direction * (2.0 / 3.0 - 0.5)).xyz);
+    vec3 rgb_b = rgb_a * 0.5 + 0.25 * (texture(image_texture, f_uvs - direction * 0.5).xyz + texture(image_texture, f_uvs + direction * 0.5).xyz);
+
+    //The wider blur is only safe if it didn't reach past the local contrast range
+    float luma_b = dot(rgb_b, LUMA);
+    if (luma_b < luma_min || luma_b > luma_max) {
+        frag_color = vec4(rgb_a, 1.0);
+    } else {
+        frag_color = vec4(rgb_b, 1.0);
+    }
+}
\ No newline at end of file
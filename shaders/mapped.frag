@@ -3,6 +3,7 @@
 in mat3 tangent_matrix;
 in vec4 shadow_space_pos;
 in vec2 f_uvs;
+in vec4 f_tint;
 
 out vec4 frag_color;
 
@@ -10,50 +11,167 @@ out vec4 frag_color;
 uniform sampler2D albedo_map;
 uniform sampler2D normal_map;
 uniform sampler2D roughness_map;
+uniform sampler2D ao_map;
+uniform sampler2D metallic_map;
 
 //Shadow map
 uniform sampler2D shadow_map;
+uniform vec2 shadow_extent;         //World-space width of the shadow map and depth of its [0, 1] range
+uniform int shadow_filter = 0;      //0 is 3x3 PCF, 1 is a Poisson disk, 2 is PCSS
 
 uniform vec4 sun_direction;
 uniform vec4 view_direction;
 
-const float AMBIENT = 0.1;
+//Debug view that outputs world-space normals as colors
+uniform bool visualize_normals = false;
+
+const float PI = 3.14159265359;
+const vec3 SUN_RADIANCE = vec3(3.0, 2.9, 2.7);
+const vec3 SKY_RADIANCE = vec3(0.21, 0.32, 0.37);
+const vec3 GROUND_RADIANCE = vec3(0.08, 0.07, 0.06);
+const float MIN_ROUGHNESS = 0.04;
+const vec3 DIELECTRIC_REFLECTANCE = vec3(0.04);
+
+//Receiver-side depth bias in world units, scaled up as the surface turns away from the sun
+const float MIN_SHADOW_BIAS = 0.005;
+const float MAX_SHADOW_BIAS = 0.04;
+const float POISSON_RADIUS = 2.5;           //In shadow map texels
+const float BLOCKER_SEARCH_RADIUS = 8.0;    //In shadow map texels
+const float MAX_PENUMBRA_RADIUS = 16.0;     //In shadow map texels
+const float SUN_ANGULAR_SIZE = 0.05;        //World units of penumbra per world unit between blocker and receiver
+
+const int POISSON_SAMPLES = 16;
+const vec2 POISSON_DISK[16] = vec2[](
+    vec2(-0.94201624, -0.39906216), vec2(0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870), vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432), vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845), vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554), vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023), vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507), vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367), vec2(0.14383161, -0.14100790)
+);
+
+//Trowbridge-Reitz GGX normal distribution
+float distribution_ggx(float n_dot_h, float roughness) {
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+//Smith's method with the Schlick-GGX approximation for each direction
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float view_term = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float light_term = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return view_term * light_term;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+}
+
+//Poisson disk rotated by a per-pixel angle so that the sample pattern turns into noise instead of banding
+mat2 poisson_rotation() {
+    float noise = fract(52.9829189 * fract(dot(gl_FragCoord.xy, vec2(0.06711056, 0.00583715))));
+    float angle = noise * 2.0 * PI;
+    return mat2(cos(angle), sin(angle), -sin(angle), cos(angle));
+}
+
+//Fraction of a Poisson disk of the given radius in texels that is in shadow
+float poisson_shadow(vec3 shadow_pos, float radius, vec2 texel_size) {
+    mat2 rotation = poisson_rotation();
+    float shadow = 0.0;
+    for (int i = 0; i < POISSON_SAMPLES; i++) {
+        vec2 offset = rotation * POISSON_DISK[i] * radius * texel_size;
+        float sampled_depth = texture(shadow_map, shadow_pos.xy + offset).r;
+        shadow += sampled_depth < shadow_pos.z ? 1.0 : 0.0;
+    }
+    return shadow / float(POISSON_SAMPLES);
+}
+
+//Percentage-closer soft shadows
+//The penumbra widens with the distance between the receiver and the average blocker
+float pcss_shadow(vec3 shadow_pos, vec2 texel_size) {
+    mat2 rotation = poisson_rotation();
+    float blocker_depth = 0.0;
+    float blockers = 0.0;
+    for (int i = 0; i < POISSON_SAMPLES; i++) {
+        vec2 offset = rotation * POISSON_DISK[i] * BLOCKER_SEARCH_RADIUS * texel_size;
+        float sampled_depth = texture(shadow_map, shadow_pos.xy + offset).r;
+        if (sampled_depth < shadow_pos.z) {
+            blocker_depth += sampled_depth;
+            blockers += 1.0;
+        }
+    }
+    if (blockers == 0.0) {
+        return 0.0;
+    }
+    blocker_depth /= blockers;
+
+    float penumbra = (shadow_pos.z - blocker_depth) * shadow_extent.y * SUN_ANGULAR_SIZE;
+    float radius = clamp(penumbra / shadow_extent.x / texel_size.x, 1.0, MAX_PENUMBRA_RADIUS);
+    return poisson_shadow(shadow_pos, radius, texel_size);
+}
 
 void main() {
-    vec3 albedo = texture(albedo_map, f_uvs).xyz;
+    vec3 albedo = texture(albedo_map, f_uvs).xyz * f_tint.rgb;
     vec3 tangent_normal = texture(normal_map, f_uvs).xyz * 2.0 - 1.0;
-    //vec3 normal = normalize(tangent_matrix * tangent_normal);
-    vec3 normal = normalize(tangent_matrix[2]);
+    vec3 normal = normalize(tangent_matrix * tangent_normal);
+
+    if (visualize_normals) {
+        frag_color = vec4(normal * 0.5 + 0.5, 1.0);
+        return;
+    }
+
+    float roughness = max(MIN_ROUGHNESS, texture(roughness_map, f_uvs).x);
+    float metallic = texture(metallic_map, f_uvs).x;
+    float ambient_occlusion = texture(ao_map, f_uvs).x;
+
+    //Cook-Torrance BRDF for the sun
+    vec3 light = normalize(sun_direction.xyz);
+    vec3 view = normalize(view_direction.xyz);
+    vec3 halfway = normalize(light + view);
+    float n_dot_l = max(0.0, dot(normal, light));
+    float n_dot_v = max(0.0001, dot(normal, view));
+    float n_dot_h = max(0.0, dot(normal, halfway));
 
     //Determine if the fragment is shadowed
-    float shadow = 0.0; 
-    vec4 adj_shadow_space_pos = shadow_space_pos * 0.5 + 0.5;
+    float shadow = 0.0;
+    vec3 adj_shadow_space_pos = shadow_space_pos.xyz * 0.5 + 0.5;
     vec2 texel_size = 1.0 / textureSize(shadow_map, 0);
-    
-    if (adj_shadow_space_pos.z > 1.0) {
-        shadow = 0.0;
-    }
-    else {
-        //Do PCF
-        //Average the 3x3 block of shadow texels centered at this pixel
-        for (int x = -1; x <= 1; x++) {
-            for (int y = -1; y <= 1; y++) {
-                float sampled_depth = texture(shadow_map, adj_shadow_space_pos.xy + vec2(x, y) * texel_size).r;
-                shadow += sampled_depth < adj_shadow_space_pos.z ? 1.0 : 0.0;
+    if (adj_shadow_space_pos.z <= 1.0) {
+        //Converted from world units to the map's depth range
+        float bias = max(MAX_SHADOW_BIAS * (1.0 - dot(normal, light)), MIN_SHADOW_BIAS) / shadow_extent.y;
+        adj_shadow_space_pos.z -= bias;
+
+        if (shadow_filter == 1) {
+            shadow = poisson_shadow(adj_shadow_space_pos, POISSON_RADIUS, texel_size);
+        } else if (shadow_filter == 2) {
+            shadow = pcss_shadow(adj_shadow_space_pos, texel_size);
+        } else {
+            //Average the 3x3 block of shadow texels centered at this pixel
+            for (int x = -1; x <= 1; x++) {
+                for (int y = -1; y <= 1; y++) {
+                    float sampled_depth = texture(shadow_map, adj_shadow_space_pos.xy + vec2(x, y) * texel_size).r;
+                    shadow += sampled_depth < adj_shadow_space_pos.z ? 1.0 : 0.0;
+                }
             }
+            shadow /= 9.0;
         }
-        shadow /= 9.0;
     }
 
-    float diffuse = max(0.0, dot(vec3(sun_direction), normal));
+    vec3 f0 = mix(DIELECTRIC_REFLECTANCE, albedo, metallic);
+    vec3 fresnel = fresnel_schlick(max(0.0, dot(halfway, view)), f0);
+    vec3 specular = distribution_ggx(n_dot_h, roughness) * geometry_smith(n_dot_v, n_dot_l, roughness) * fresnel / (4.0 * n_dot_v * n_dot_l + 0.0001);
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo / PI;
+    vec3 direct = (diffuse + specular) * SUN_RADIANCE * n_dot_l * (1.0 - shadow);
 
-    float roughness = texture(roughness_map, f_uvs).x;
-    vec4 halfway = normalize(view_direction + sun_direction);
-    float specular_angle = max(0.0, dot(vec3(halfway), normal));
-    float specular_coefficient = 100 / (500 * roughness + 0.01);
-    float specular = pow(specular_angle, specular_coefficient);
+    //Hemispheric ambient light blended between the ground and sky colors
+    vec3 hemisphere = mix(GROUND_RADIANCE, SKY_RADIANCE, normal.y * 0.5 + 0.5);
+    vec3 ambient_fresnel = fresnel_schlick(n_dot_v, f0);
+    vec3 ambient = ((1.0 - ambient_fresnel) * (1.0 - metallic) * albedo + ambient_fresnel) * hemisphere * ambient_occlusion;
 
-    //frag_color = vec4(normal / 2.0 + 0.5, 1.0);
-    vec3 final_color = ((specular + diffuse) * (1.0 - shadow) + AMBIENT) * albedo;
-    frag_color = vec4(final_color, 1.0);
+    frag_color = vec4(direct + ambient, 1.0);
 }
\ No newline at end of file
@@ -9,10 +9,12 @@ layout (location = 4) in vec2 uv;
 out mat3 tangent_matrix;
 out vec4 shadow_space_pos;
 out vec2 f_uvs;
+out vec4 f_tint;
 
 uniform mat4 mvp;
 uniform mat4 model_matrix;
 uniform mat4 shadow_matrix;
+uniform vec2 uv_offset;
 
 void main() {
     mat4 normal_matrix = transpose(mat4(inverse(mat3(model_matrix))));
@@ -23,6 +25,7 @@ void main() {
 
     shadow_space_pos = shadow_matrix * model_matrix * vec4(position, 1.0);
 
-    f_uvs = uv;
+    f_uvs = uv + uv_offset;
+    f_tint = vec4(1.0);
     gl_Position = mvp * vec4(position, 1.0);
 }
\ No newline at end of file
@@ -7,12 +7,15 @@ layout (location = 2) in vec3 bitangent;
 layout (location = 3) in vec3 normal;
 layout (location = 4) in vec2 uv;
 
-//Instanced array
+//Instanced arrays
 layout (location = 5) in mat4 model_matrix;
+layout (location = 9) in vec2 uv_offset;        //Scrolls v on the mesh's +x half by x and its -x half by y, zero unless the mesh scrolls its texture
+layout (location = 10) in vec4 tint;            //Multiplies the albedo, set to white for meshes without a tint buffer
 
 out mat3 tangent_matrix;
 out vec4 shadow_space_pos;
 out vec2 f_uvs;
+out vec4 f_tint;
 
 uniform mat4 view_projection;
 uniform mat4 shadow_matrix;
@@ -28,6 +31,8 @@ void main() {
     
     shadow_space_pos = shadow_matrix * world_space_pos;
     
-    f_uvs = uv;
+    //The tread meshes span both sides of the hull, and each side rolls at its own speed while turning
+    f_uvs = uv + vec2(0.0, position.x > 0.0 ? uv_offset.x : uv_offset.y);
+    f_tint = tint;
     gl_Position = view_projection * world_space_pos;
 }
\ No newline at end of file
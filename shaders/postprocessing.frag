@@ -5,18 +5,15 @@ in vec2 f_uvs;
 out vec4 frag_color;
 
 uniform sampler2D image_texture;
-uniform bool monochrome = false;
+uniform float exposure = 1.0;
 
-void main() {
-    vec2 texel_size = 1.0 / textureSize(image_texture, 0);
+//Narkowicz's fit of the ACES filmic curve
+//Maps HDR color into [0, 1] before the framebuffer's sRGB conversion
+vec3 tone_map(vec3 color) {
+    color *= exposure;
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
 
-    //Check which effect is enabled and apply it
-    if (monochrome) {
-        vec3 intermediate = texture(image_texture, f_uvs).xyz;
-        float average = (intermediate.r + intermediate.g + intermediate.b) / 3.0;
-        frag_color = vec4(average, average, average, 1.0);
-    } else {
-        vec3 intermediate = texture(image_texture, f_uvs).xyz;
-        frag_color = vec4(intermediate, 1.0);
-    }
+void main() {
+    frag_color = vec4(tone_map(texture(image_texture, f_uvs).xyz), 1.0);
 }
\ No newline at end of file
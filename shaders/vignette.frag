@@ -0,0 +1,21 @@
+#version 330 core
+
+in vec2 f_uvs;
+
+out vec4 frag_color;
+
+uniform sampler2D image_texture;
+
+const float INNER_RADIUS = 0.45;
+const float OUTER_RADIUS = 0.85;
+const float STRENGTH = 0.45;
+
+void main() {
+    vec3 color = texture(image_texture, f_uvs).xyz;
+
+    //Measured so that the darkening stays circular on a wide screen
+    vec2 size = vec2(textureSize(image_texture, 0));
+    vec2 centered = (f_uvs - 0.5) * vec2(size.x / size.y, 1.0);
+    float falloff = smoothstep(INNER_RADIUS, OUTER_RADIUS, length(centered));
+    frag_color = vec4(color * (1.0 - falloff * STRENGTH), 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,279 @@
+//Packed asset archive shared by the game and the pack_assets tool
+//Everything is little-endian
+//[magic: "WTPK"] [entry count: u32] then for each entry [path length: u32] [path: utf-8 with '/' separators] [data length: u64] [data]
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub const ARCHIVE_NAME: &str = "assets.pack";
+pub const ASSET_DIRECTORIES: [&str; 5] = ["shaders", "models", "textures", "music", "sounds"];
+const MAGIC: &[u8; 4] = b"WTPK";
+const STAMP_NAME: &str = ".pack_stamp";
+
+//Packs every file under root's asset directories into a single archive
+//Returns the number of files packed
+//Only the pack_assets tool calls this
+#[allow(dead_code)]
+pub fn pack(root: &Path, output: &Path) -> io::Result<usize> {
+    let mut paths = Vec::new();
+    for directory in ASSET_DIRECTORIES.iter() {
+        collect_files(root, &root.join(directory), &mut paths)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(output)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(paths.len() as u32).to_le_bytes())?;
+    for path in paths.iter() {
+        let data = fs::read(root.join(path))?;
+        writer.write_all(&(path.len() as u32).to_le_bytes())?;
+        writer.write_all(path.as_bytes())?;
+        writer.write_all(&(data.len() as u64).to_le_bytes())?;
+        writer.write_all(&data)?;
+    }
+    writer.flush()?;
+    Ok(paths.len())
+}
+
+//Recursively gathers the paths of every file under directory, relative to root
+fn collect_files(root: &Path, directory: &Path, paths: &mut Vec<String>) -> io::Result<()> {
+    if !directory.is_dir() { return Ok(()); }
+
+    let mut entries: Vec<_> = fs::read_dir(directory)?.collect::<Result<_, _>>()?;
+    entries.sort_by_key(|entry| entry.path());
+    for entry in entries {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(root, &path, paths)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let components: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+            paths.push(components.join("/"));
+        }
+    }
+    Ok(())
+}
+
+//An archive read fully into memory along with where each entry's data starts and ends
+//Every length in the file is checked against what's actually there before anything is allocated for it
+pub struct Archive {
+    data: Vec<u8>,
+    entries: Vec<(String, Range<usize>)>
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> io::Result<Self> {
+        let mut cursor = 0;
+        if take(&data, &mut cursor, MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not an asset archive".to_string()));
+        }
+
+        let count = read_u32(&data, &mut cursor)? as usize;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let path_length = read_u32(&data, &mut cursor)? as usize;
+            let path = match String::from_utf8(take(&data, &mut cursor, path_length)?.to_vec()) {
+                Ok(p) => { p }
+                Err(_) => { return Err(invalid_data("entry path isn't utf-8".to_string())); }
+            };
+
+            //Refuse to write anywhere outside of the destination when unpacking
+            if !Path::new(&path).components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(invalid_data(format!("entry \"{}\" escapes the archive", path)));
+            }
+
+            let data_length = read_u64(&data, &mut cursor)?;
+            if data_length > (data.len() - cursor) as u64 {
+                return Err(invalid_data(format!("entry \"{}\" claims {} bytes but only {} are left", path, data_length, data.len() - cursor)));
+            }
+            let start = cursor;
+            cursor += data_length as usize;
+            entries.push((path, start..cursor));
+        }
+
+        if cursor != data.len() {
+            return Err(invalid_data(format!("{} bytes of garbage after the last entry", data.len() - cursor)));
+        }
+        Ok(Archive { data, entries })
+    }
+
+    pub fn entry_count(&self) -> usize { self.entries.len() }
+
+    //64-bit FNV-1a of the whole archive, which names its directory in the unpack cache when the file's modification time isn't available
+    pub fn content_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for byte in self.data.iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        hash
+    }
+}
+
+//The loaders all take paths, so the game still needs the archive's files on disk
+//Each distinct archive is extracted into its own directory under cache, so that files from other versions of the pack never resolve
+//The directory is named by the archive's size and modification time, so a warm start only has to stat the archive rather than read all of it
+//Returns that directory, which is reused as-is if an earlier run finished extracting into it
+pub fn unpack(archive_path: &Path, cache: &Path) -> io::Result<PathBuf> {
+    let metadata = fs::metadata(archive_path)?;
+    let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+    let (key, archive) = match modified {
+        Some(modified) => { (format!("{:016x}-{}", metadata.len(), modified.as_nanos()), None) }
+        None => {
+            //Without a modification time the only way to tell versions apart is by their contents
+            let archive = Archive::open(archive_path)?;
+            (format!("{:016x}", archive.content_hash()), Some(archive))
+        }
+    };
+    let destination = cache.join(&key);
+    if destination.join(STAMP_NAME).is_file() {
+        return Ok(destination);
+    }
+    let archive = match archive {
+        Some(archive) => { archive }
+        None => { Archive::open(archive_path)? }
+    };
+
+    //Extract next to the destination and move it into place once it's complete, so that a crash or another instance can't leave a half-written root behind
+    let staging = cache.join(format!("{}.{}.partial", key, std::process::id()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir_all(&staging)?;
+    for (path, range) in archive.entries.iter() {
+        let output = staging.join(path);
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(output, &archive.data[range.clone()])?;
+    }
+    fs::write(staging.join(STAMP_NAME), archive.entry_count().to_string())?;
+
+    match fs::rename(&staging, &destination) {
+        Ok(_) => { Ok(destination) }
+        Err(e) => {
+            //Losing the race to another instance unpacking the same archive is fine
+            let _ = fs::remove_dir_all(&staging);
+            if destination.join(STAMP_NAME).is_file() { Ok(destination) } else { Err(e) }
+        }
+    }
+}
+
+//Advances cursor past the next length bytes of data, failing instead of reading past the end
+fn take<'a>(data: &'a [u8], cursor: &mut usize, length: usize) -> io::Result<&'a [u8]> {
+    if length > data.len() - *cursor {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "archive is truncated"));
+    }
+    let bytes = &data[*cursor..*cursor + length];
+    *cursor += length;
+    Ok(bytes)
+}
+
+fn read_u32(data: &[u8], cursor: &mut usize) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(take(data, cursor, 4)?);
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(data: &[u8], cursor: &mut usize) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(take(data, cursor, 8)?);
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    //Builds an archive in memory the same way pack() writes one
+    fn archive_bytes(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for (path, data) in entries.iter() {
+            bytes.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data.as_bytes());
+        }
+        bytes
+    }
+
+    fn scratch_directory(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("whee_tanks_archive_test_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn indexes_every_entry() {
+        let archive = Archive::from_bytes(archive_bytes(&[("shaders/a.vert", "void main() {}"), ("sounds/b.wav", "")])).unwrap();
+        assert_eq!(archive.entry_count(), 2);
+        assert_eq!(&archive.data[archive.entries[0].1.clone()], b"void main() {}");
+        assert_eq!(archive.entries[1].1.len(), 0);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut bytes = archive_bytes(&[]);
+        bytes[0] = b'X';
+        assert!(Archive::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_entries_longer_than_the_file() {
+        let mut bytes = archive_bytes(&[("models/tank.ozy", "abcd")]);
+        let length_offset = bytes.len() - 4 - 8;
+        bytes[length_offset..length_offset + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(Archive::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_archives() {
+        let bytes = archive_bytes(&[("models/tank.ozy", "abcd")]);
+        for length in 0..bytes.len() {
+            assert!(Archive::from_bytes(bytes[..length].to_vec()).is_err(), "accepted {} of {} bytes", length, bytes.len());
+        }
+    }
+
+    #[test]
+    fn rejects_more_entries_than_are_present() {
+        let mut bytes = archive_bytes(&[("a", "1")]);
+        bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Archive::from_bytes(bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_escaping_paths() {
+        assert!(Archive::from_bytes(archive_bytes(&[("../evil", "")])).is_err());
+        assert!(Archive::from_bytes(archive_bytes(&[("/etc/evil", "")])).is_err());
+    }
+
+    #[test]
+    fn each_archive_unpacks_into_its_own_directory() {
+        let directory = scratch_directory("unpack");
+        fs::create_dir_all(&directory).unwrap();
+        let (old_path, new_path) = (directory.join("old.pack"), directory.join("new.pack"));
+        fs::write(&old_path, archive_bytes(&[("textures/a.png", "old"), ("textures/removed.png", "gone")])).unwrap();
+        fs::write(&new_path, archive_bytes(&[("textures/a.png", "new")])).unwrap();
+
+        let cache = directory.join("cache");
+        let old_root = unpack(&old_path, &cache).unwrap();
+        let new_root = unpack(&new_path, &cache).unwrap();
+        assert_ne!(old_root, new_root);
+        assert_eq!(fs::read(new_root.join("textures/a.png")).unwrap(), b"new");
+        assert!(!new_root.join("textures/removed.png").exists());
+
+        //Unpacking the same archive again reuses its directory
+        assert_eq!(unpack(&new_path, &cache).unwrap(), new_root);
+        let _ = fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,237 @@
+use gl::types::*;
+use ozy_engine::glutil;
+use std::env;
+use std::fmt;
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use crate::archive;
+
+//Everything that can go wrong while loading a file from disk
+#[derive(Debug)]
+pub enum AssetError {
+    Missing(String),                        //Path of a file that doesn't exist
+    Unreadable(String),                     //Path of a file that exists but couldn't be parsed
+    Undecodable(String, String),            //Path of an audio file and the decoder's complaint
+    MissingBones(String, Vec<String>),      //Path of a model and the names of the required bones it lacks
+    ShaderFailed(String, String),           //Shader files and the compile or link log
+    MissingUniforms(String, Vec<String>)    //Shader files and the expected uniforms that aren't active in the program
+}
+
+impl AssetError {
+    //Picks between Missing and Unreadable for a file that failed to load
+    pub fn from_path(path: &str) -> Self {
+        if Path::new(path).exists() {
+            AssetError::Unreadable(path.to_string())
+        } else {
+            AssetError::Missing(path.to_string())
+        }
+    }
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AssetError::Missing(path) => { write!(f, "\"{}\" doesn't exist", path) }
+            AssetError::Unreadable(path) => { write!(f, "\"{}\" couldn't be parsed", path) }
+            AssetError::Undecodable(path, reason) => { write!(f, "\"{}\" couldn't be decoded: {}", path, reason) }
+            AssetError::MissingBones(path, names) => { write!(f, "\"{}\" is missing required bones: {}", path, names.join(", ")) }
+            AssetError::ShaderFailed(paths, log) => { write!(f, "\"{}\" failed to build:\n{}", paths, log) }
+            AssetError::MissingUniforms(paths, names) => { write!(f, "\"{}\" has no active uniforms named: {}", paths, names.join(", ")) }
+        }
+    }
+}
+
+//Turns asset paths like "shaders/mapped.vert" into paths that can actually be opened
+//The root comes from the --asset-root flag, then the WHEE_TANKS_ASSET_ROOT environment variable, then the executable's directory, then the working directory
+//A root holding a packed archive instead of loose files has the archive unpacked into the user's cache directory, which becomes the real root
+#[derive(Clone, Debug)]
+pub struct AssetResolver {
+    root: PathBuf
+}
+
+impl AssetResolver {
+    const ROOT_FLAG: &'static str = "--asset-root";
+    const ROOT_VARIABLE: &'static str = "WHEE_TANKS_ASSET_ROOT";
+    const CACHE_DIRECTORY: &'static str = "whee_tanks_assets";
+
+    pub fn from_environment() -> Self {
+        //An explicitly requested root is used even if it looks empty so that the missing assets get reported
+        let args: Vec<String> = env::args().collect();
+        let explicit_root = match args.iter().position(|arg| arg == Self::ROOT_FLAG) {
+            Some(i) => { args.get(i + 1).map(PathBuf::from) }
+            None => { env::var_os(Self::ROOT_VARIABLE).map(PathBuf::from) }
+        };
+        if let Some(root) = explicit_root {
+            return Self::open(root);
+        }
+
+        if let Some(directory) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+            if Self::has_loose_assets(&directory) || directory.join(archive::ARCHIVE_NAME).is_file() {
+                return Self::open(directory);
+            }
+        }
+
+        Self::open(PathBuf::from("."))
+    }
+
+    //path is either a directory or an archive file
+    //Loose files win over an archive in the same directory so that edits show up without repacking
+    fn open(path: PathBuf) -> Self {
+        let archive_path = if path.is_file() {
+            path.clone()
+        } else if !Self::has_loose_assets(&path) && path.join(archive::ARCHIVE_NAME).is_file() {
+            path.join(archive::ARCHIVE_NAME)
+        } else {
+            return AssetResolver { root: path };
+        };
+
+        match archive::unpack(&archive_path, &Self::cache_directory()) {
+            Ok(root) => { AssetResolver { root } }
+            Err(e) => {
+                println!("Couldn't unpack \"{}\":\n{}", archive_path.display(), e);
+                let root = archive_path.parent().map(Path::to_path_buf).unwrap_or_default();
+                AssetResolver { root }
+            }
+        }
+    }
+
+    //The user's own cache directory so that other users can't swap out the unpacked files, with the shared temp directory as a last resort
+    fn cache_directory() -> PathBuf {
+        let user_cache = env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")));
+        match user_cache {
+            Some(directory) => { directory.join(Self::CACHE_DIRECTORY) }
+            None => {
+                let user = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default();
+                env::temp_dir().join(format!("{}_{}", Self::CACHE_DIRECTORY, user))
+            }
+        }
+    }
+
+    fn has_loose_assets(directory: &Path) -> bool {
+        archive::ASSET_DIRECTORIES.iter().any(|name| directory.join(name).is_dir())
+    }
+
+    pub fn resolve(&self, path: &str) -> String {
+        self.root.join(path).to_string_lossy().into_owned()
+    }
+}
+
+//Prints one summary of every problem found while loading assets
+pub fn log_missing_assets(errors: &[AssetError]) {
+    if errors.len() == 0 { return; }
+
+    println!("{} problem(s) found while loading assets, missing files were replaced with fallbacks:", errors.len());
+    for error in errors.iter() {
+        println!("\t{}", error);
+    }
+}
+
+//Magenta and black checkerboard that's hard to miss in a scene
+pub unsafe fn checkerboard_texture() -> GLuint {
+    let size = 8;
+    let mut pixels = Vec::with_capacity(size * size * 4);
+    for y in 0..size {
+        for x in 0..size {
+            if (x + y) % 2 == 0 {
+                pixels.extend_from_slice(&[255u8, 0, 255, 255]);
+            } else {
+                pixels.extend_from_slice(&[0u8, 0, 0, 255]);
+            }
+        }
+    }
+    create_texture(size as GLsizei, &pixels)
+}
+
+//1x1 texture of a single color
+pub unsafe fn solid_texture(color: [u8; 4]) -> GLuint {
+    create_texture(1, &color)
+}
+
+//Color grading LUT that maps every color to itself
+//The 3D table is laid out as a strip of LUT_SIZE slices, one per blue value, with red across each slice and green going up
+pub unsafe fn identity_lut_texture() -> GLuint {
+    const LUT_SIZE: usize = 16;
+    let step = 255.0 / (LUT_SIZE - 1) as f32;
+    let mut pixels = Vec::with_capacity(LUT_SIZE * LUT_SIZE * LUT_SIZE * 4);
+    for g in 0..LUT_SIZE {
+        for b in 0..LUT_SIZE {
+            for r in 0..LUT_SIZE {
+                pixels.extend_from_slice(&[(r as f32 * step) as u8, (g as f32 * step) as u8, (b as f32 * step) as u8, 255]);
+            }
+        }
+    }
+    let tex = create_texture_with_size((LUT_SIZE * LUT_SIZE) as GLsizei, LUT_SIZE as GLsizei, &pixels);
+    let params = [
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE),
+        (gl::TEXTURE_MIN_FILTER, gl::LINEAR),
+        (gl::TEXTURE_MAG_FILTER, gl::LINEAR)
+    ];
+    glutil::apply_texture_parameters(&params);
+    tex
+}
+
+unsafe fn create_texture(size: GLsizei, pixels: &[u8]) -> GLuint {
+    let tex = create_texture_with_size(size, size, pixels);
+    let params = [
+        (gl::TEXTURE_WRAP_S, gl::REPEAT),
+        (gl::TEXTURE_WRAP_T, gl::REPEAT),
+        (gl::TEXTURE_MIN_FILTER, gl::NEAREST),
+        (gl::TEXTURE_MAG_FILTER, gl::NEAREST)
+    ];
+    glutil::apply_texture_parameters(&params);
+    tex
+}
+
+//Leaves the new texture bound
+unsafe fn create_texture_with_size(width: GLsizei, height: GLsizei, pixels: &[u8]) -> GLuint {
+    let mut tex = 0;
+    gl::GenTextures(1, &mut tex);
+    gl::BindTexture(gl::TEXTURE_2D, tex);
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::RGBA8 as GLint,
+        width,
+        height,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        &pixels[0] as *const u8 as *const c_void
+    );
+    tex
+}
+
+//Appends an axis-aligned cube to a vertex array in the same layout as .ozy meshes
+//[position, tangent, bitangent, normal, uv]
+pub fn append_cube(vertices: &mut Vec<f32>, indices: &mut Vec<u16>, center: &glm::TVec3<f32>, half_size: f32) {
+    //Normal and tangent of each face
+    let faces = [
+        (glm::vec3(0.0, 1.0, 0.0), glm::vec3(1.0, 0.0, 0.0)),
+        (glm::vec3(0.0, -1.0, 0.0), glm::vec3(-1.0, 0.0, 0.0)),
+        (glm::vec3(1.0, 0.0, 0.0), glm::vec3(0.0, 0.0, -1.0)),
+        (glm::vec3(-1.0, 0.0, 0.0), glm::vec3(0.0, 0.0, 1.0)),
+        (glm::vec3(0.0, 0.0, 1.0), glm::vec3(1.0, 0.0, 0.0)),
+        (glm::vec3(0.0, 0.0, -1.0), glm::vec3(-1.0, 0.0, 0.0))
+    ];
+    let corners: [(f32, f32); 4] = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)];
+
+    for (normal, tangent) in faces.iter() {
+        //Same handedness as the floor plane so the winding order matches
+        let bitangent = glm::cross(tangent, normal);
+        let base = (vertices.len() / 14) as u16;
+        for (u, v) in corners.iter() {
+            let position = center + (normal + tangent * *u + bitangent * *v) * half_size;
+            vertices.extend_from_slice(&[
+                position.x, position.y, position.z,
+                tangent.x, tangent.y, tangent.z,
+                bitangent.x, bitangent.y, bitangent.z,
+                normal.x, normal.y, normal.z,
+                (u + 1.0) * 0.5, (v + 1.0) * 0.5
+            ]);
+        }
+        indices.extend_from_slice(&[base, base + 1, base + 2, base + 3, base + 2, base + 1]);
+    }
+}
@@ -0,0 +1,134 @@
+use rodio::{Decoder, Device, Sink, Source};
+use rodio::source::{Buffered, ChannelVolume};
+use std::fs::File;
+use std::io::BufReader;
+use std::mem;
+use crate::assets::{AssetError, AssetResolver};
+
+type SoundClip = Buffered<Decoder<BufReader<File>>>;
+
+//Every sound effect the game can play
+//The discriminant doubles as the index into SoundEffects::clips
+#[derive(Debug, Clone, Copy)]
+pub enum SoundEffect {
+    Fire,
+    ShellImpact,
+    Ricochet,
+    TankDestroyed,
+    UIClick
+}
+
+impl SoundEffect {
+    const PATHS: [&'static str; 5] = [
+        "sounds/fire.wav",
+        "sounds/shell_impact.wav",
+        "sounds/ricochet.wav",
+        "sounds/tank_destroyed.wav",
+        "sounds/ui_click.wav"
+    ];
+}
+
+//A fixed pool of sinks that sound effects are played through
+//When every sink is busy the one that was started longest ago gets cut off
+pub struct SoundEffects {
+    device: Option<Device>,
+    clips: Vec<Option<SoundClip>>,
+    sinks: Vec<Sink>,
+    next_sink: usize,
+    missing: Vec<AssetError>,
+    pub volume: f32
+}
+
+impl SoundEffects {
+    const SINK_COUNT: usize = 16;
+    const PAN_WIDTH: f32 = 10.0;                //Distance along x from the listener at which a sound is fully in one ear
+    const ATTENUATION_DISTANCE: f32 = 30.0;     //Distance along x from the listener at which a sound becomes silent
+    const MIN_ATTENUATION: f32 = 0.2;
+
+    pub fn new(device: Option<Device>, volume: f32, resolver: &AssetResolver) -> Self {
+        //Effects that fail to load are silent
+        let mut clips = Vec::with_capacity(SoundEffect::PATHS.len());
+        let mut missing = Vec::new();
+        for path in SoundEffect::PATHS.iter() {
+            let path = resolver.resolve(path);
+            let clip = match File::open(&path) {
+                Ok(f) => {
+                    match Decoder::new(BufReader::new(f)) {
+                        Ok(decoder) => { Some(decoder.buffered()) }
+                        Err(e) => {
+                            missing.push(AssetError::Undecodable(path.clone(), e.to_string()));
+                            None
+                        }
+                    }
+                }
+                Err(_) => {
+                    missing.push(AssetError::from_path(&path));
+                    None
+                }
+            };
+            clips.push(clip);
+        }
+
+        let sinks = match &device {
+            Some(d) => { (0..Self::SINK_COUNT).map(|_| Sink::new(d)).collect() }
+            None => { Vec::new() }
+        };
+
+        SoundEffects {
+            device,
+            clips,
+            sinks,
+            next_sink: 0,
+            missing,
+            volume
+        }
+    }
+
+    //Hands over every sound effect that failed to load
+    pub fn take_missing(&mut self) -> Vec<AssetError> {
+        mem::take(&mut self.missing)
+    }
+
+    //Plays a sound effect centered between both ears at full volume
+    pub fn play(&mut self, effect: SoundEffect) {
+        self.play_panned(effect, 0.0, 1.0);
+    }
+
+    //Plays a sound effect panned and attenuated by how far the emitter is from the listener along the x-axis
+    pub fn play_at(&mut self, effect: SoundEffect, emitter_position: &glm::TVec3<f32>, listener_position: &glm::TVec3<f32>) {
+        let offset = emitter_position.x - listener_position.x;
+        let pan = f32::max(-1.0, f32::min(1.0, offset / Self::PAN_WIDTH));
+        let attenuation = f32::max(Self::MIN_ATTENUATION, 1.0 - offset.abs() / Self::ATTENUATION_DISTANCE);
+        self.play_panned(effect, pan, attenuation);
+    }
+
+    //pan goes from -1.0 (left) to 1.0 (right)
+    fn play_panned(&mut self, effect: SoundEffect, pan: f32, attenuation: f32) {
+        let device = match &self.device {
+            Some(d) => { d }
+            None => { return; }
+        };
+
+        let clip = match &self.clips[effect as usize] {
+            Some(c) => { c.clone() }
+            None => { return; }
+        };
+
+        //Constant-power panning
+        let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+        let gain = self.volume * attenuation;
+        let source = ChannelVolume::new(clip, vec![angle.cos() * gain, angle.sin() * gain]);
+
+        //Prefer an idle sink, otherwise steal the next one in line
+        let index = match self.sinks.iter().position(|sink| sink.empty()) {
+            Some(i) => { i }
+            None => {
+                let i = self.next_sink;
+                self.sinks[i] = Sink::new(device);
+                i
+            }
+        };
+        self.next_sink = (index + 1) % self.sinks.len();
+        self.sinks[index].append(source);
+    }
+}
@@ -0,0 +1,26 @@
+//Packs the game's shaders, models, textures, music and sounds into a single archive
+//Usage: pack_assets [asset root] [output file]
+use std::env;
+use std::path::PathBuf;
+
+//The game only needs unpacking
+#[allow(dead_code)]
+#[path = "../archive.rs"]
+mod archive;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let root = PathBuf::from(args.get(1).map(|s| s.as_str()).unwrap_or("."));
+    let output = match args.get(2) {
+        Some(path) => { PathBuf::from(path) }
+        None => { root.join(archive::ARCHIVE_NAME) }
+    };
+
+    match archive::pack(&root, &output) {
+        Ok(count) => { println!("Packed {} files into \"{}\"", count, output.display()); }
+        Err(e) => {
+            println!("Couldn't pack \"{}\":\n{}", root.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
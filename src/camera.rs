@@ -0,0 +1,153 @@
+//Where the scene is viewed from and how it's projected onto the screen
+
+//What the camera is looking at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    Overhead,           //Fixed on the center of the arena
+    Follow              //Smoothly tracks the player's tank, staying over the arena
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Orthographic,
+    Perspective
+}
+
+pub struct Camera {
+    pub mode: CameraMode,
+    pub projection: Projection,
+    focus: glm::TVec3<f32>,             //World-space point at the center of the screen
+    zoom: f32,                          //How many times closer than the default view
+    zoom_target: f32,
+    trauma: f32,                        //[0, 1] amount of screen shake, which falls off over time
+    shake_time: f32,
+    bounds: (f32, f32),                 //Half size of the area in x and z that Follow keeps the focus inside
+    view_from_world: glm::TMat4<f32>,
+    pub clipping_from_world: glm::TMat4<f32>,
+    pub world_from_clipping: glm::TMat4<f32>
+}
+
+impl Camera {
+    pub const SHELL_IMPACT_TRAUMA: f32 = 0.1;
+    pub const EXPLOSION_TRAUMA: f32 = 0.6;
+    const ORTHO_SIZE: f32 = 5.0;                //Half the height of the view at a zoom of 1.0
+    const FIELD_OF_VIEW: f32 = std::f32::consts::FRAC_PI_4;    //Vertical, in radians
+    const FOLLOW_TIME: f32 = 0.25;              //Time for the focus to cover about 63% of the way to the player
+    const ZOOM_TIME: f32 = 0.1;
+    const ZOOM_STEP: f32 = 1.1;                 //Zoom multiplier per notch of the mouse wheel
+    const MIN_ZOOM: f32 = 0.5;
+    const MAX_ZOOM: f32 = 3.0;
+    const TRAUMA_DECAY: f32 = 1.5;              //Trauma lost per second
+    const MAX_SHAKE_OFFSET: f32 = 0.3;          //World units at full trauma
+    const MAX_SHAKE_ROLL: f32 = 0.03;           //Radians at full trauma
+
+    pub fn new(bounds: (f32, f32), aspect_ratio: f32) -> Self {
+        let mut camera = Camera {
+            mode: CameraMode::Overhead,
+            projection: Projection::Orthographic,
+            focus: glm::zero(),
+            zoom: 1.0,
+            zoom_target: 1.0,
+            trauma: 0.0,
+            shake_time: 0.0,
+            bounds,
+            view_from_world: glm::identity(),
+            clipping_from_world: glm::identity(),
+            world_from_clipping: glm::identity()
+        };
+        camera.update(0.0, None, aspect_ratio);
+        camera
+    }
+
+    //Back to the overhead view of the whole arena with no zoom or shake
+    //The mode and projection are settings so they're kept
+    pub fn reset(&mut self, aspect_ratio: f32) {
+        self.focus = glm::zero();
+        self.zoom = 1.0;
+        self.zoom_target = 1.0;
+        self.trauma = 0.0;
+        self.update(0.0, None, aspect_ratio);
+    }
+
+    pub fn zoom_by(&mut self, notches: f32) {
+        self.zoom_target = (self.zoom_target * Self::ZOOM_STEP.powf(notches)).max(Self::MIN_ZOOM).min(Self::MAX_ZOOM);
+    }
+
+    //Shakes the screen, with bigger amounts stacking up to a full shake
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = f32::min(1.0, self.trauma + amount);
+    }
+
+    //Direction pointing from the scene towards the viewer
+    pub fn look_direction(&self) -> glm::TVec4<f32> {
+        glm::affine_inverse(self.view_from_world) * glm::vec4(0.0, 0.0, 1.0, 0.0)
+    }
+
+    pub fn position(&self) -> glm::TVec3<f32> {
+        glm::vec4_to_vec3(&(glm::affine_inverse(self.view_from_world) * glm::vec4(0.0, 0.0, 0.0, 1.0)))
+    }
+
+    //The ray under a point in clipping space, as an origin and a normalized direction pointing into the scene
+    //Works for either projection because both ends are found by unprojecting
+    pub fn ray_from_clipping(&self, clipping_point: &glm::TVec2<f32>) -> (glm::TVec3<f32>, glm::TVec3<f32>) {
+        let near = self.world_from_clipping * glm::vec4(clipping_point.x, clipping_point.y, -1.0, 1.0);
+        let far = self.world_from_clipping * glm::vec4(clipping_point.x, clipping_point.y, 1.0, 1.0);
+        let near = glm::vec4_to_vec3(&near) / near.w;
+        let far = glm::vec4_to_vec3(&far) / far.w;
+        (near, glm::normalize(&(far - near)))
+    }
+
+    //Moves the focus, zoom and shake along, then rebuilds the matrices
+    //target is what Follow mode tracks
+    pub fn update(&mut self, delta_time: f32, target: Option<&glm::TVec3<f32>>, aspect_ratio: f32) {
+        let focus_target = match (self.mode, target) {
+            (CameraMode::Follow, Some(t)) => {
+                glm::vec3(
+                    t.x.max(-self.bounds.0).min(self.bounds.0),
+                    0.0,
+                    t.z.max(-self.bounds.1).min(self.bounds.1)
+                )
+            }
+            (CameraMode::Follow, None) => { self.focus }
+            (CameraMode::Overhead, _) => { glm::zero() }
+        };
+        self.focus += (focus_target - self.focus) * (1.0 - f32::exp(-delta_time / Self::FOLLOW_TIME));
+        self.zoom += (self.zoom_target - self.zoom) * (1.0 - f32::exp(-delta_time / Self::ZOOM_TIME));
+
+        //Shake with a few incommensurate sine waves so the motion doesn't visibly repeat
+        self.trauma = f32::max(0.0, self.trauma - delta_time * Self::TRAUMA_DECAY);
+        self.shake_time += delta_time;
+        let shake = self.trauma * self.trauma;
+        let t = self.shake_time * 40.0;
+        let shake_offset = glm::vec3(
+            (t * 1.13).sin() + (t * 2.71).sin() * 0.5,
+            0.0,
+            (t * 1.37).sin() + (t * 3.17).sin() * 0.5
+        ) * (shake * Self::MAX_SHAKE_OFFSET / 1.5);
+        let shake_roll = (t * 0.97).sin() * shake * Self::MAX_SHAKE_ROLL;
+
+        //The overhead direction of the original fixed camera
+        let eye_direction = glm::normalize(&glm::vec3(0.0, 1.5, -1.0));
+        let half_height = Self::ORTHO_SIZE / self.zoom;
+        let focus = self.focus + shake_offset;
+        let (eye_distance, clipping_from_view) = match self.projection {
+            Projection::Orthographic => {
+                let distance = glm::length(&glm::vec3(0.0, 1.5, -1.0));
+                (distance, glm::ortho(-half_height * aspect_ratio, half_height * aspect_ratio, -half_height, half_height, -Self::ORTHO_SIZE, Self::ORTHO_SIZE * 2.0))
+            }
+            Projection::Perspective => {
+                //Far enough back that the focal plane shows as much as the orthographic view
+                let distance = half_height / (Self::FIELD_OF_VIEW * 0.5).tan();
+                (distance, glm::perspective(aspect_ratio, Self::FIELD_OF_VIEW, 0.1, distance + Self::ORTHO_SIZE * 3.0))
+            }
+        };
+
+        self.view_from_world = glm::rotation(shake_roll, &glm::vec3(0.0, 0.0, 1.0)) *
+                                glm::mat4(-1.0, 0.0, 0.0, 0.0,
+                                        0.0, 1.0, 0.0, 0.0,
+                                        0.0, 0.0, 1.0, 0.0,
+                                        0.0, 0.0, 0.0, 1.0) * glm::look_at(&(focus + eye_direction * eye_distance), &focus, &glm::vec3(0.0, 1.0, 0.0));
+        self.clipping_from_world = clipping_from_view * self.view_from_world;
+        self.world_from_clipping = glm::inverse(&self.clipping_from_world);
+    }
+}
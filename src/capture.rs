@@ -0,0 +1,199 @@
+//Reading rendered frames back from the GL and moving them in and out of PNG files
+use gl::types::*;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::os::raw::c_void;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+//8-bit sRGB RGBA pixels, top row first
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+impl Image {
+    //Reads back a texture holding linear color, encoding it to sRGB the same way FRAMEBUFFER_SRGB does on the way to the screen
+    pub unsafe fn from_texture(texture: GLuint) -> Self {
+        let (mut width, mut height) = (0, 0);
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut height);
+
+        let mut linear = vec![0.0f32; (width * height * 4) as usize];
+        gl::GetTexImage(gl::TEXTURE_2D, 0, gl::RGBA, gl::FLOAT, linear.as_mut_ptr() as *mut c_void);
+
+        //The GL stores the bottom row first
+        let row_length = width as usize * 4;
+        let mut pixels = Vec::with_capacity(linear.len());
+        for row in linear.chunks(row_length).rev() {
+            for (i, value) in row.iter().enumerate() {
+                let value = if i % 4 == 3 { *value } else { linear_to_srgb(*value) };
+                pixels.push((value.max(0.0).min(1.0) * 255.0 + 0.5) as u8);
+            }
+        }
+
+        Image {
+            width: width as u32,
+            height: height as u32,
+            pixels
+        }
+    }
+
+    //Reads back what's currently in the default framebuffer's back buffer, UI included
+    //The default framebuffer already holds sRGB values so they're copied as-is
+    pub unsafe fn from_default_framebuffer(size: (u32, u32)) -> Self {
+        let mut raw = vec![0u8; (size.0 * size.1 * 4) as usize];
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl::ReadBuffer(gl::BACK);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, size.0 as GLsizei, size.1 as GLsizei, gl::RGBA, gl::UNSIGNED_BYTE, raw.as_mut_ptr() as *mut c_void);
+
+        //The GL stores the bottom row first, and the window has no meaningful alpha
+        let mut pixels = Vec::with_capacity(raw.len());
+        for row in raw.chunks(size.0 as usize * 4).rev() {
+            for pixel in row.chunks(4) {
+                pixels.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 255]);
+            }
+        }
+
+        Image {
+            width: size.0,
+            height: size.1,
+            pixels
+        }
+    }
+
+    pub fn load_png(path: &Path) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info().map_err(invalid_data)?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).map_err(invalid_data)?;
+
+        let pixels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::RGBA, png::BitDepth::Eight) => { data }
+            (png::ColorType::RGB, png::BitDepth::Eight) => {
+                data.chunks(3).flat_map(|rgb| vec![rgb[0], rgb[1], rgb[2], 255]).collect()
+            }
+            (color_type, bit_depth) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} at {:?} bits isn't supported", color_type, bit_depth)));
+            }
+        };
+
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            pixels
+        })
+    }
+
+    pub fn save_png(&self, path: &Path) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(invalid_data)?;
+        writer.write_image_data(&self.pixels).map_err(invalid_data)
+    }
+}
+
+//Writes the image to the screenshots directory under a name made from the current time
+pub fn save_screenshot(image: &Image) {
+    let directory = Path::new(SCREENSHOT_DIRECTORY);
+    let path = directory.join(format!("{}.png", timestamp()));
+    match fs::create_dir_all(directory).and_then(|_| image.save_png(&path)) {
+        Ok(_) => { println!("Saved screenshot to \"{}\"", path.display()); }
+        Err(e) => { println!("Couldn't save screenshot to \"{}\": {}", path.display(), e); }
+    }
+}
+
+//Dumps every frame as a numbered PNG while the simulation steps by a fixed amount per frame
+//Made for recording trailers and bug reports, so the game runs as slowly as it needs to
+pub struct FrameCapture {
+    directory: PathBuf,
+    pub frame_time: f32,
+    next_frame: usize
+}
+
+impl FrameCapture {
+    const DIRECTORY_FLAG: &'static str = "--capture-frames";
+    const RATE_FLAG: &'static str = "--capture-fps";
+    const DEFAULT_RATE: f32 = 60.0;
+
+    //Returns None unless --capture-frames <directory> was passed
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = env::args().collect();
+        let flag_value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+
+        let directory = PathBuf::from(flag_value(Self::DIRECTORY_FLAG)?);
+        if let Err(e) = fs::create_dir_all(&directory) {
+            println!("Not capturing frames because \"{}\" couldn't be created: {}", directory.display(), e);
+            return None;
+        }
+
+        let rate = match flag_value(Self::RATE_FLAG).map(|value| value.parse::<f32>()) {
+            Some(Ok(r)) if r > 0.0 => { r }
+            Some(_) => {
+                println!("{} expects a positive number, using {}", Self::RATE_FLAG, Self::DEFAULT_RATE);
+                Self::DEFAULT_RATE
+            }
+            None => { Self::DEFAULT_RATE }
+        };
+
+        println!("Capturing frames to \"{}\" at {} fps", directory.display(), rate);
+        Some(FrameCapture {
+            directory,
+            frame_time: 1.0 / rate,
+            next_frame: 0
+        })
+    }
+
+    pub fn save(&mut self, image: &Image) {
+        let path = self.directory.join(format!("frame_{:06}.png", self.next_frame));
+        if let Err(e) = image.save_png(&path) {
+            println!("Couldn't save \"{}\": {}", path.display(), e);
+        }
+        self.next_frame += 1;
+    }
+}
+
+//The current UTC time as YYYY-MM-DD_HH-MM-SS.mmm
+fn timestamp() -> String {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (days, time_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+    //Howard Hinnant's civil-from-days algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}",
+        year, month, day,
+        time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn invalid_data<E: ToString>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
@@ -0,0 +1,262 @@
+use crate::structs::CollisionSphere;
+
+//An infinite plane defined by a unit normal and the plane's signed distance from the origin along that normal
+//Only the side the normal points towards is considered solid-free, so spheres are stopped from crossing to the back side
+#[derive(Debug)]
+pub struct CollisionPlane {
+    pub normal: glm::TVec4<f32>,
+    pub distance: f32
+}
+
+impl CollisionPlane {
+    pub fn new(normal: glm::TVec4<f32>, point: &glm::TVec4<f32>) -> Self {
+        let normal = glm::normalize(&normal);
+        let distance = glm::dot(&normal, &glm::vec4(point.x, point.y, point.z, 0.0));
+        CollisionPlane {
+            normal,
+            distance
+        }
+    }
+
+    pub fn signed_distance(&self, point: &glm::TVec4<f32>) -> f32 {
+        glm::dot(&self.normal, &glm::vec4(point.x, point.y, point.z, 0.0)) - self.distance
+    }
+}
+
+//Reflects a velocity vector about the plane with the given normal
+pub fn reflect(velocity: &glm::TVec4<f32>, normal: &glm::TVec4<f32>) -> glm::TVec4<f32> {
+    velocity - normal * (2.0 * glm::dot(velocity, normal))
+}
+
+//Sweeps a sphere of the given radius from start to end against a stationary sphere
+//Returns the time of impact as a fraction in [0, 1] of the distance travelled, or None if they never touch
+pub fn sweep_sphere_sphere(start: &glm::TVec4<f32>, end: &glm::TVec4<f32>, radius: f32, target: &CollisionSphere) -> Option<f32> {
+    let combined_radius = radius + target.radius;
+    let direction = glm::vec4_to_vec3(&(end - start));
+    let offset = glm::vec4_to_vec3(&(start - target.origin));
+
+    //The spheres are already overlapping at the start of the sweep
+    let c = glm::dot(&offset, &offset) - combined_radius * combined_radius;
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+
+    //Reject if the sphere isn't moving or is moving away from the target
+    let a = glm::dot(&direction, &direction);
+    let b = glm::dot(&offset, &direction);
+    if a == 0.0 || b >= 0.0 {
+        return None;
+    }
+
+    //Solve the quadratic for the first point of contact
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / a;
+    if t <= 1.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+//Sweeps a sphere of the given radius from start to end against a plane
+//Returns the time of impact as a fraction in [0, 1] of the distance travelled, or None if they never touch
+pub fn sweep_sphere_plane(start: &glm::TVec4<f32>, end: &glm::TVec4<f32>, radius: f32, plane: &CollisionPlane) -> Option<f32> {
+    let start_distance = plane.signed_distance(start);
+    let end_distance = plane.signed_distance(end);
+
+    //Spheres moving parallel to or away from the plane can't hit it
+    if end_distance >= start_distance {
+        return None;
+    }
+
+    //The sphere is already touching the plane at the start of the sweep
+    if start_distance <= radius {
+        return Some(0.0);
+    }
+
+    if end_distance < radius {
+        Some((start_distance - radius) / (start_distance - end_distance))
+    } else {
+        None
+    }
+}
+
+//A box on the ground plane that can be rotated about the y-axis
+#[derive(Debug)]
+pub struct OrientedBox {
+    pub center: glm::TVec3<f32>,
+    pub axes: [glm::TVec3<f32>; 2],         //The box's local x and z axes in world space
+    pub half_extents: glm::TVec2<f32>
+}
+
+//Separating axis test between two oriented boxes on the ground plane
+//Returns the minimum translation that pushes a out of b, or None if they aren't overlapping
+pub fn box_penetration(a: &OrientedBox, b: &OrientedBox) -> Option<glm::TVec3<f32>> {
+    let offset = a.center - b.center;
+    let test_axes = [a.axes[0], a.axes[1], b.axes[0], b.axes[1]];
+
+    let mut min_overlap = f32::INFINITY;
+    let mut min_axis = glm::zero();
+    for axis in test_axes.iter() {
+        let distance = glm::dot(&offset, axis);
+        let radius_a = a.half_extents.x * glm::dot(&a.axes[0], axis).abs() + a.half_extents.y * glm::dot(&a.axes[1], axis).abs();
+        let radius_b = b.half_extents.x * glm::dot(&b.axes[0], axis).abs() + b.half_extents.y * glm::dot(&b.axes[1], axis).abs();
+
+        //Found a separating axis
+        let overlap = radius_a + radius_b - distance.abs();
+        if overlap <= 0.0 {
+            return None;
+        }
+
+        if overlap < min_overlap {
+            min_overlap = overlap;
+            min_axis = if distance < 0.0 { -axis } else { *axis };
+        }
+    }
+
+    Some(min_axis * min_overlap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+    use crate::structs::CollisionEntity;
+
+
+    fn sphere_at_origin(radius: f32) -> CollisionSphere {
+        CollisionSphere {
+            origin: glm::vec4(0.0, 0.0, 0.0, 1.0),
+            radius,
+            target: CollisionEntity::Tank(0)
+        }
+    }
+
+    //The arena's left wall, with its open side facing +x
+    fn wall() -> CollisionPlane {
+        CollisionPlane::new(glm::vec4(1.0, 0.0, 0.0, 0.0), &glm::vec4(-5.0, 0.0, 0.0, 1.0))
+    }
+
+    #[test]
+    fn sphere_head_on_hit() {
+        //Centers are 1.0 apart at contact, which is at x = -1.0 after covering 2.0 of the 6.0 travelled
+        let t = sweep_sphere_sphere(&glm::vec4(-3.0, 0.0, 0.0, 1.0), &glm::vec4(3.0, 0.0, 0.0, 1.0), 0.5, &sphere_at_origin(0.5));
+        assert_close(t.unwrap(), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn sphere_grazing_miss() {
+        let t = sweep_sphere_sphere(&glm::vec4(-3.0, 1.01, 0.0, 1.0), &glm::vec4(3.0, 1.01, 0.0, 1.0), 0.5, &sphere_at_origin(0.5));
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn sphere_falls_short() {
+        let t = sweep_sphere_sphere(&glm::vec4(-3.0, 0.0, 0.0, 1.0), &glm::vec4(-1.5, 0.0, 0.0, 1.0), 0.5, &sphere_at_origin(0.5));
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn sphere_already_touching() {
+        //Overlapping spheres report an immediate hit whichever way they're moving
+        let t = sweep_sphere_sphere(&glm::vec4(0.9, 0.0, 0.0, 1.0), &glm::vec4(3.0, 0.0, 0.0, 1.0), 0.5, &sphere_at_origin(0.5));
+        assert_eq!(t, Some(0.0));
+    }
+
+    #[test]
+    fn sphere_moving_away() {
+        let t = sweep_sphere_sphere(&glm::vec4(2.0, 0.0, 0.0, 1.0), &glm::vec4(5.0, 0.0, 0.0, 1.0), 0.5, &sphere_at_origin(0.5));
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn plane_head_on_hit() {
+        //The sphere's surface reaches the wall after 4.5 of the 6.0 travelled
+        let t = sweep_sphere_plane(&glm::vec4(0.0, 0.0, 0.0, 1.0), &glm::vec4(-6.0, 0.0, 0.0, 1.0), 0.5, &wall());
+        assert_close(t.unwrap(), 0.75);
+    }
+
+    #[test]
+    fn plane_glancing_hit() {
+        //Only the motion along the normal matters
+        let t = sweep_sphere_plane(&glm::vec4(0.0, 0.0, 0.0, 1.0), &glm::vec4(-6.0, 0.0, 10.0, 1.0), 0.5, &wall());
+        assert_close(t.unwrap(), 0.75);
+    }
+
+    #[test]
+    fn plane_falls_short() {
+        let t = sweep_sphere_plane(&glm::vec4(0.0, 0.0, 0.0, 1.0), &glm::vec4(-4.0, 0.0, 0.0, 1.0), 0.5, &wall());
+        assert_eq!(t, None);
+    }
+
+    #[test]
+    fn plane_already_touching() {
+        let t = sweep_sphere_plane(&glm::vec4(-4.6, 0.0, 0.0, 1.0), &glm::vec4(-5.0, 0.0, 0.0, 1.0), 0.5, &wall());
+        assert_eq!(t, Some(0.0));
+    }
+
+    #[test]
+    fn plane_moving_away() {
+        //Even a sphere that starts touching the wall is free to leave it
+        assert_eq!(sweep_sphere_plane(&glm::vec4(-4.6, 0.0, 0.0, 1.0), &glm::vec4(0.0, 0.0, 0.0, 1.0), 0.5, &wall()), None);
+        assert_eq!(sweep_sphere_plane(&glm::vec4(0.0, 0.0, 0.0, 1.0), &glm::vec4(0.0, 0.0, 6.0, 1.0), 0.5, &wall()), None);
+    }
+
+    fn unit_box(center: glm::TVec3<f32>, angle: f32) -> OrientedBox {
+        let (sin, cos) = angle.sin_cos();
+        OrientedBox {
+            center,
+            axes: [glm::vec3(cos, 0.0, -sin), glm::vec3(sin, 0.0, cos)],
+            half_extents: glm::vec2(1.0, 1.0)
+        }
+    }
+
+    #[test]
+    fn separated_boxes() {
+        let a = unit_box(glm::vec3(2.5, 0.0, 0.0), 0.0);
+        let b = unit_box(glm::zero(), 0.0);
+        assert!(box_penetration(&a, &b).is_none());
+    }
+
+    #[test]
+    fn touching_boxes_are_separated() {
+        let a = unit_box(glm::vec3(2.0, 0.0, 0.0), 0.0);
+        let b = unit_box(glm::zero(), 0.0);
+        assert!(box_penetration(&a, &b).is_none());
+    }
+
+    #[test]
+    fn overlap_pushes_along_the_shallowest_axis() {
+        //Overlapping by 0.5 in x and 1.5 in z, so a gets pushed out along +x
+        let a = unit_box(glm::vec3(1.5, 0.0, 0.5), 0.0);
+        let b = unit_box(glm::zero(), 0.0);
+        let push = box_penetration(&a, &b).unwrap();
+        assert!(glm::distance(&push, &glm::vec3(0.5, 0.0, 0.0)) < 0.0001);
+
+        //Swapping the boxes flips the push
+        let push = box_penetration(&b, &a).unwrap();
+        assert!(glm::distance(&push, &glm::vec3(-0.5, 0.0, 0.0)) < 0.0001);
+    }
+
+    #[test]
+    fn rotated_box_separated_by_its_own_axis() {
+        //Overlapping along both of b's axes, so only a's diagonal axis can find the gap
+        let a = unit_box(glm::vec3(1.8, 0.0, 1.8), std::f32::consts::FRAC_PI_4);
+        let b = unit_box(glm::zero(), 0.0);
+        assert!(box_penetration(&a, &b).is_none());
+    }
+
+    #[test]
+    fn rotated_box_overlapping() {
+        //The diamond's corner reaches 1.414 toward b, past b's face at 1.0
+        let a = unit_box(glm::vec3(2.3, 0.0, 0.0), std::f32::consts::FRAC_PI_4);
+        let b = unit_box(glm::zero(), 0.0);
+        let push = box_penetration(&a, &b).unwrap();
+        assert!(push.x > 0.0);
+        assert!((glm::length(&push) - (1.0 + 2.0f32.sqrt() - 2.3)).abs() < 0.0001);
+    }
+}
@@ -0,0 +1,273 @@
+//Marks left on the floor by combat
+//Like the particles, nothing in here touches OpenGL, the results are copied into an InstancedMesh for drawing
+use crate::rng::Rng;
+
+//Describes what a kind of decal looks like and how long it sticks around
+pub struct DecalStyle {
+    pub size: (f32, f32),               //Width across and length along the decal's direction
+    pub lifetime: f32,                  //Seconds until the decal is gone
+    pub fade_time: f32,                 //Seconds at the end of the lifetime spent fading out
+    pub color: [f32; 4]
+}
+
+impl DecalStyle {
+    pub const SCORCH: DecalStyle = DecalStyle {
+        size: (2.2, 2.2),
+        lifetime: 30.0,
+        fade_time: 10.0,
+        color: [0.05, 0.04, 0.03, 0.85]
+    };
+
+    pub const IMPACT: DecalStyle = DecalStyle {
+        size: (0.35, 0.35),
+        lifetime: 12.0,
+        fade_time: 4.0,
+        color: [0.1, 0.08, 0.06, 0.7]
+    };
+
+    pub const TREAD: DecalStyle = DecalStyle {
+        size: (0.25, 0.35),
+        lifetime: 6.0,
+        fade_time: 3.0,
+        color: [0.15, 0.12, 0.08, 0.35]
+    };
+}
+
+pub struct Decal {
+    pub position: glm::TVec3<f32>,
+    pub angle: f32,                     //Rotation about the y-axis
+    pub age: f32,
+    style: &'static DecalStyle
+}
+
+impl Decal {
+    pub fn is_alive(&self) -> bool {
+        self.age < self.style.lifetime
+    }
+
+    //Full strength until the fade starts, then linearly down to nothing
+    pub fn opacity(&self) -> f32 {
+        let remaining = self.style.lifetime - self.age;
+        f32::max(0.0, f32::min(1.0, remaining / self.style.fade_time))
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        let mut color = self.style.color;
+        color[3] *= self.opacity();
+        color
+    }
+}
+
+//Fixed number of decals where a new decal replaces the oldest one once the ring is full
+pub struct DecalRing {
+    decals: Vec<Decal>,
+    next: usize,                        //Slot the next decal goes into once the ring is full
+    max_decals: usize,
+    rng: Rng
+}
+
+impl DecalRing {
+    pub const MAX_DECALS: usize = 512;
+    pub const FLOATS_PER_TRANSFORM: usize = 16;
+    pub const FLOATS_PER_COLOR: usize = 4;
+    pub const TREAD_SPACING: f32 = 0.3;             //Distance a tank rolls between tread marks
+    const HEIGHT: f32 = 0.001;                      //Lift off of the floor, on top of the polygon offset they're drawn with
+
+    pub fn new(max_decals: usize) -> Self {
+        DecalRing {
+            decals: Vec::with_capacity(max_decals),
+            next: 0,
+            max_decals,
+            rng: Rng::new(0x2545_F491)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+        self.next = 0;
+    }
+
+    //Places a decal on the floor below position
+    //A None angle gives the decal a random rotation, which keeps round marks from looking stamped
+    pub fn add(&mut self, style: &'static DecalStyle, position: &glm::TVec3<f32>, angle: Option<f32>) {
+        let angle = match angle {
+            Some(a) => { a }
+            None => { self.rng.random() * std::f32::consts::TAU }
+        };
+        let decal = Decal {
+            position: glm::vec3(position.x, Self::HEIGHT, position.z),
+            angle,
+            age: 0.0,
+            style
+        };
+
+        if self.decals.len() < self.max_decals {
+            self.decals.push(decal);
+        } else {
+            self.decals[self.next] = decal;
+            self.next = (self.next + 1) % self.max_decals;
+        }
+    }
+
+    //Leaves a pair of tread marks under a tank at position heading along forward
+    pub fn add_tread_marks(&mut self, position: &glm::TVec3<f32>, forward: &glm::TVec3<f32>, half_width: f32) {
+        let side = glm::cross(forward, &glm::vec3(0.0, 1.0, 0.0)) * half_width;
+        let angle = f32::atan2(forward.x, forward.z);
+        self.add(&DecalStyle::TREAD, &(position + side), Some(angle));
+        self.add(&DecalStyle::TREAD, &(position - side), Some(angle));
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        for decal in self.decals.iter_mut() {
+            decal.age += delta_time;
+        }
+    }
+
+    //Writes each living decal's transform and color into the given buffers for instanced rendering
+    //Dead decals keep their slot in the ring until they're overwritten, they just aren't drawn
+    pub fn fill_instance_buffers(&self, transforms: &mut Vec<f32>, colors: &mut Vec<f32>) {
+        transforms.clear();
+        colors.clear();
+
+        //Oldest first so that newer decals are blended on top
+        let (newer, older) = self.decals.split_at(self.next);
+        for decal in older.iter().chain(newer.iter()) {
+            if !decal.is_alive() { continue; }
+
+            let (width, length) = decal.style.size;
+            let (sin, cos) = decal.angle.sin_cos();
+            let p = decal.position;
+            transforms.extend_from_slice(&[
+                cos * width, 0.0, -sin * width, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                sin * length, 0.0, cos * length, 0.0,
+                p.x, p.y, p.z, 1.0
+            ]);
+            colors.extend_from_slice(&decal.color());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+
+    const TEST_STYLE: DecalStyle = DecalStyle {
+        size: (1.0, 2.0),
+        lifetime: 10.0,
+        fade_time: 4.0,
+        color: [0.2, 0.4, 0.6, 0.8]
+    };
+
+    const SHORT_LIVED: DecalStyle = DecalStyle { lifetime: 1.0, fade_time: 0.5, ..TEST_STYLE };
+
+    fn add_at(ring: &mut DecalRing, style: &'static DecalStyle, x: f32) {
+        ring.add(style, &glm::vec3(x, 5.0, 0.0), Some(0.0));
+    }
+
+    //x position of every decal that would be drawn, in draw order
+    fn drawn_positions(ring: &DecalRing) -> Vec<f32> {
+        let (mut transforms, mut colors) = (Vec::new(), Vec::new());
+        ring.fill_instance_buffers(&mut transforms, &mut colors);
+        assert_eq!(transforms.len() / DecalRing::FLOATS_PER_TRANSFORM, colors.len() / DecalRing::FLOATS_PER_COLOR);
+        transforms.chunks(DecalRing::FLOATS_PER_TRANSFORM).map(|t| t[12]).collect()
+    }
+
+    #[test]
+    fn decals_sit_on_the_floor() {
+        let mut ring = DecalRing::new(4);
+        add_at(&mut ring, &TEST_STYLE, 1.0);
+        assert_close(ring.decals[0].position.y, DecalRing::HEIGHT);
+    }
+
+    #[test]
+    fn draws_in_the_order_added_until_full() {
+        let mut ring = DecalRing::new(3);
+        add_at(&mut ring, &TEST_STYLE, 0.0);
+        add_at(&mut ring, &TEST_STYLE, 1.0);
+        assert_eq!(drawn_positions(&ring), vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn full_ring_replaces_the_oldest() {
+        let mut ring = DecalRing::new(3);
+        for x in 0..5 {
+            add_at(&mut ring, &TEST_STYLE, x as f32);
+        }
+        assert_eq!(ring.decals.len(), 3);
+        assert_eq!(drawn_positions(&ring), vec![2.0, 3.0, 4.0]);
+
+        //Wrapping all the way around lands back on the first slot
+        add_at(&mut ring, &TEST_STYLE, 5.0);
+        add_at(&mut ring, &TEST_STYLE, 6.0);
+        assert_eq!(ring.next, 1);
+        assert_eq!(drawn_positions(&ring), vec![4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn opacity_holds_then_fades() {
+        let mut ring = DecalRing::new(1);
+        add_at(&mut ring, &TEST_STYLE, 0.0);
+
+        ring.update(5.0);
+        assert_eq!(ring.decals[0].opacity(), 1.0);
+
+        //Halfway through the fade
+        ring.update(3.0);
+        assert_close(ring.decals[0].opacity(), 0.5);
+        let color = ring.decals[0].color();
+        assert_eq!(&color[..3], &TEST_STYLE.color[..3]);
+        assert_close(color[3], TEST_STYLE.color[3] * 0.5);
+
+        ring.update(2.0);
+        assert!(!ring.decals[0].is_alive());
+        assert_eq!(ring.decals[0].opacity(), 0.0);
+    }
+
+    #[test]
+    fn dead_decals_are_skipped() {
+        let mut ring = DecalRing::new(3);
+        add_at(&mut ring, &SHORT_LIVED, 0.0);
+        add_at(&mut ring, &TEST_STYLE, 1.0);
+        ring.update(2.0);
+        add_at(&mut ring, &TEST_STYLE, 2.0);
+        assert_eq!(drawn_positions(&ring), vec![1.0, 2.0]);
+
+        //The dead decal's slot is the oldest, so it's the next one overwritten
+        add_at(&mut ring, &TEST_STYLE, 3.0);
+        assert_eq!(drawn_positions(&ring), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn tread_marks_straddle_the_tank() {
+        let mut ring = DecalRing::new(4);
+        ring.add_tread_marks(&glm::vec3(1.0, 0.0, 1.0), &glm::vec3(0.0, 0.0, 1.0), 0.5);
+        let mut xs = drawn_positions(&ring);
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_close(xs[0], 0.5);
+        assert_close(xs[1], 1.5);
+    }
+
+    #[test]
+    fn random_angles_stay_in_a_full_turn() {
+        let mut ring = DecalRing::new(64);
+        for _ in 0..64 {
+            ring.add(&TEST_STYLE, &glm::zero(), None);
+        }
+        assert!(ring.decals.iter().all(|d| d.angle >= 0.0 && d.angle <= std::f32::consts::TAU));
+    }
+
+    #[test]
+    fn clear_empties_the_ring() {
+        let mut ring = DecalRing::new(2);
+        for x in 0..3 {
+            add_at(&mut ring, &TEST_STYLE, x as f32);
+        }
+        ring.clear();
+        assert!(drawn_positions(&ring).is_empty());
+        add_at(&mut ring, &TEST_STYLE, 7.0);
+        assert_eq!(drawn_positions(&ring), vec![7.0]);
+    }
+}
@@ -0,0 +1,43 @@
+//The volume a projection can see, for skipping objects that wouldn't reach the screen
+
+//Six inward-facing planes as (normal, distance), so a point is inside when dot(normal, point) + distance >= 0 for all of them
+pub struct Frustum {
+    planes: [glm::TVec4<f32>; 6]
+}
+
+impl Frustum {
+    //Pulls the planes out of a clipping-from-world matrix, which works for orthographic and perspective projections alike
+    pub fn from_matrix(clipping_from_world: &glm::TMat4<f32>) -> Self {
+        let rows = [
+            glm::row(clipping_from_world, 0),
+            glm::row(clipping_from_world, 1),
+            glm::row(clipping_from_world, 2),
+            glm::row(clipping_from_world, 3)
+        ];
+        let mut planes = [
+            rows[3] + rows[0],          //Left
+            rows[3] - rows[0],          //Right
+            rows[3] + rows[1],          //Bottom
+            rows[3] - rows[1],          //Top
+            rows[3] + rows[2],          //Near
+            rows[3] - rows[2]           //Far
+        ];
+
+        //Normalize so that plane distances are in world units
+        for plane in planes.iter_mut() {
+            let length = glm::length(&glm::vec3(plane.x, plane.y, plane.z));
+            if length > 0.0 {
+                *plane /= length;
+            }
+        }
+
+        Frustum {
+            planes
+        }
+    }
+
+    //False only when the sphere is entirely outside of one of the planes
+    pub fn intersects_sphere(&self, center: &glm::TVec3<f32>, radius: f32) -> bool {
+        self.planes.iter().all(|plane| plane.x * center.x + plane.y * center.y + plane.z * center.z + plane.w >= -radius)
+    }
+}
@@ -0,0 +1,150 @@
+//Owning handles for GL objects, which delete their object when dropped
+//Every live object is recorded so that debug builds can report whatever is still around at shutdown
+use gl::types::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GLObjectKind {
+    Texture,
+    Buffer,
+    VertexArray,
+    Framebuffer,
+    Program
+}
+
+thread_local! {
+    //The GL context belongs to the main thread, so that's the only thread that creates objects
+    static LIVE_OBJECTS: RefCell<Vec<(GLObjectKind, GLuint)>> = RefCell::new(Vec::new());
+}
+
+//Records a newly created object as live
+//Name zero is the GL's default object, which is never owned
+pub fn track(kind: GLObjectKind, name: GLuint) {
+    if name == 0 { return; }
+    LIVE_OBJECTS.with(|objects| objects.borrow_mut().push((kind, name)));
+}
+
+pub fn untrack(kind: GLObjectKind, name: GLuint) {
+    LIVE_OBJECTS.with(|objects| {
+        let mut objects = objects.borrow_mut();
+        if let Some(i) = objects.iter().position(|object| *object == (kind, name)) {
+            objects.swap_remove(i);
+        }
+    });
+}
+
+#[derive(Debug)]
+pub struct Texture {
+    name: GLuint
+}
+
+impl Texture {
+    pub unsafe fn new() -> Self {
+        let mut name = 0;
+        gl::GenTextures(1, &mut name);
+        Self::from_name(name)
+    }
+
+    //Takes ownership of a texture created elsewhere, like by glutil::load_texture()
+    pub fn from_name(name: GLuint) -> Self {
+        track(GLObjectKind::Texture, name);
+        Texture { name }
+    }
+
+    pub fn name(&self) -> GLuint { self.name }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        untrack(GLObjectKind::Texture, self.name);
+        unsafe { gl::DeleteTextures(1, &self.name); }
+    }
+}
+
+#[derive(Debug)]
+pub struct Buffer {
+    name: GLuint
+}
+
+impl Buffer {
+    pub unsafe fn new() -> Self {
+        let mut name = 0;
+        gl::GenBuffers(1, &mut name);
+        Self::from_name(name)
+    }
+
+    pub fn from_name(name: GLuint) -> Self {
+        track(GLObjectKind::Buffer, name);
+        Buffer { name }
+    }
+
+    pub fn name(&self) -> GLuint { self.name }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        untrack(GLObjectKind::Buffer, self.name);
+        unsafe { gl::DeleteBuffers(1, &self.name); }
+    }
+}
+
+//A vertex array object along with the vertex and index buffers it was created with
+//Buffers attached later, like instance data, are owned by whatever attached them
+#[derive(Debug)]
+pub struct VertexArray {
+    name: GLuint,
+    _buffers: Vec<Buffer>
+}
+
+impl VertexArray {
+    //Takes ownership of a VAO made by glutil::create_vertex_array_object() or prims, which also made a vertex buffer for attribute 0 and an index buffer
+    //Leaves the VAO bound
+    pub unsafe fn from_name(name: GLuint) -> Self {
+        gl::BindVertexArray(name);
+        let (mut vertex_buffer, mut index_buffer) = (0, 0);
+        gl::GetVertexAttribiv(0, gl::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING, &mut vertex_buffer);
+        gl::GetIntegerv(gl::ELEMENT_ARRAY_BUFFER_BINDING, &mut index_buffer);
+
+        track(GLObjectKind::VertexArray, name);
+        VertexArray {
+            name,
+            _buffers: [vertex_buffer, index_buffer].iter().filter(|b| **b != 0).map(|b| Buffer::from_name(*b as GLuint)).collect()
+        }
+    }
+
+    pub fn name(&self) -> GLuint { self.name }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        untrack(GLObjectKind::VertexArray, self.name);
+        unsafe { gl::DeleteVertexArrays(1, &self.name); }
+    }
+}
+
+//Declare one of these before the window and anything made with the GL
+//Being dropped last, it lists every GL object that outlived everything else in debug builds
+pub struct LeakReport;
+
+impl Drop for LeakReport {
+    fn drop(&mut self) {
+        if !cfg!(debug_assertions) { return; }
+
+        LIVE_OBJECTS.with(|objects| {
+            let objects = objects.borrow();
+            if objects.len() == 0 { return; }
+
+            let mut by_kind: BTreeMap<GLObjectKind, Vec<GLuint>> = BTreeMap::new();
+            for (kind, name) in objects.iter() {
+                by_kind.entry(*kind).or_insert_with(Vec::new).push(*name);
+            }
+
+            println!("{} GL objects were still alive at shutdown:", objects.len());
+            for (kind, mut names) in by_kind.into_iter() {
+                names.sort();
+                println!("\t{:?} x{}: {:?}", kind, names.len(), names);
+            }
+        });
+    }
+}
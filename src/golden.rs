@@ -0,0 +1,181 @@
+//Golden-image mode: renders a scripted scene in a hidden window and compares the finished frame with a stored PNG
+//The window still needs a display, so on a machine without a GPU run it under Xvfb with Mesa's llvmpipe:
+//    LIBGL_ALWAYS_SOFTWARE=1 xvfb-run whee_tanks --golden duel
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use crate::capture::Image;
+use crate::input::Command;
+use crate::structs::Tank;
+
+//The scenes that golden images exist for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GoldenScene {
+    Arena,          //The main menu backdrop
+    Duel,           //The player driving towards the enemy and firing
+    Paused          //The duel with the pause menu's blur over it
+}
+
+impl GoldenScene {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "arena" => { Some(GoldenScene::Arena) }
+            "duel" => { Some(GoldenScene::Duel) }
+            "paused" => { Some(GoldenScene::Paused) }
+            _ => { None }
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GoldenScene::Arena => { "arena" }
+            GoldenScene::Duel => { "duel" }
+            GoldenScene::Paused => { "paused" }
+        }
+    }
+
+    //The commands to inject on the given frame
+    pub fn commands(self, frame: usize) -> Vec<Command> {
+        match (self, frame) {
+            (GoldenScene::Duel, 0) | (GoldenScene::Paused, 0) => { vec![Command::StartPlaying, Command::MovePlayerTank(-Tank::SPEED)] }
+            (GoldenScene::Duel, 20) | (GoldenScene::Paused, 20) => { vec![Command::MovePlayerTank(Tank::SPEED), Command::Fire] }
+            (GoldenScene::Paused, 30) => { vec![Command::PauseGame] }
+            _ => { Vec::new() }
+        }
+    }
+
+    //The frame whose render gets compared
+    pub fn capture_frame(self) -> usize {
+        match self {
+            GoldenScene::Arena => { 2 }
+            GoldenScene::Duel => { 30 }
+            GoldenScene::Paused => { 32 }
+        }
+    }
+}
+
+//A golden-image run requested on the command line
+pub struct GoldenRun {
+    pub scene: GoldenScene,
+    directory: PathBuf,
+    update: bool,               //Overwrite the golden instead of comparing against it
+    channel_tolerance: u8       //Largest per-channel difference that still counts as a match
+}
+
+impl GoldenRun {
+    pub const RESOLUTION: (u32, u32) = (640, 360);
+    pub const FRAME_TIME: f32 = 1.0 / 60.0;         //Simulation steps by this much every frame regardless of how long the frame took
+    const MAX_MISMATCHED_FRACTION: f32 = 0.001;     //Rasterizers disagree on the odd edge pixel
+    const SCENE_FLAG: &'static str = "--golden";
+    const DIRECTORY_FLAG: &'static str = "--golden-dir";
+    const TOLERANCE_FLAG: &'static str = "--golden-tolerance";
+    const UPDATE_FLAG: &'static str = "--update-goldens";
+    const DEFAULT_DIRECTORY: &'static str = "goldens";
+    const DEFAULT_TOLERANCE: u8 = 8;
+
+    //Returns None when the game should run normally
+    pub fn from_args() -> Option<Self> {
+        let args: Vec<String> = env::args().collect();
+        let flag_value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+
+        let scene_name = flag_value(Self::SCENE_FLAG)?;
+        let scene = match GoldenScene::from_name(scene_name) {
+            Some(s) => { s }
+            None => {
+                println!("\"{}\" isn't a golden scene, expected one of: arena, duel, paused", scene_name);
+                std::process::exit(2);
+            }
+        };
+
+        let channel_tolerance = match flag_value(Self::TOLERANCE_FLAG).map(|value| value.parse()) {
+            Some(Ok(t)) => { t }
+            Some(Err(_)) => {
+                println!("{} expects a whole number from 0 to 255", Self::TOLERANCE_FLAG);
+                std::process::exit(2);
+            }
+            None => { Self::DEFAULT_TOLERANCE }
+        };
+
+        Some(GoldenRun {
+            scene,
+            directory: PathBuf::from(flag_value(Self::DIRECTORY_FLAG).map(String::as_str).unwrap_or(Self::DEFAULT_DIRECTORY)),
+            update: args.iter().any(|arg| arg == Self::UPDATE_FLAG),
+            channel_tolerance
+        })
+    }
+
+    //Compares the captured frame with the scene's golden, or replaces the golden when updating
+    //Mismatches leave the actual frame and a difference image next to the golden
+    //Returns the process exit code
+    pub fn finish(&self, image: &Image) -> i32 {
+        if let Err(e) = fs::create_dir_all(&self.directory) {
+            println!("Couldn't create \"{}\": {}", self.directory.display(), e);
+            return 1;
+        }
+        let golden_path = self.directory.join(format!("{}.png", self.scene.name()));
+        let actual_path = self.directory.join(format!("{}.actual.png", self.scene.name()));
+        let diff_path = self.directory.join(format!("{}.diff.png", self.scene.name()));
+
+        if self.update {
+            return match image.save_png(&golden_path) {
+                Ok(_) => {
+                    println!("Wrote \"{}\"", golden_path.display());
+                    0
+                }
+                Err(e) => {
+                    println!("Couldn't write \"{}\": {}", golden_path.display(), e);
+                    1
+                }
+            };
+        }
+
+        let golden = match Image::load_png(&golden_path) {
+            Ok(g) => { g }
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    println!("\"{}\" doesn't exist yet, rerun with {} under LIBGL_ALWAYS_SOFTWARE=1 to create it, then commit it", golden_path.display(), Self::UPDATE_FLAG);
+                } else {
+                    println!("Couldn't load \"{}\": {}", golden_path.display(), e);
+                }
+                let _ = image.save_png(&actual_path);
+                return 1;
+            }
+        };
+
+        if golden.width != image.width || golden.height != image.height {
+            println!("\"{}\" is {}x{} but the render is {}x{}", golden_path.display(), golden.width, golden.height, image.width, image.height);
+            let _ = image.save_png(&actual_path);
+            return 1;
+        }
+
+        //Mismatched pixels are red in the difference image, everything else is a dimmed copy of the golden
+        let mut mismatched = 0;
+        let mut diff = Vec::with_capacity(golden.pixels.len());
+        for (expected, actual) in golden.pixels.chunks(4).zip(image.pixels.chunks(4)) {
+            let difference = expected.iter().zip(actual.iter()).map(|(e, a)| (*e as i32 - *a as i32).abs() as u8).max().unwrap_or(0);
+            if difference > self.channel_tolerance {
+                mismatched += 1;
+                diff.extend_from_slice(&[255, 0, 0, 255]);
+            } else {
+                diff.extend_from_slice(&[expected[0] / 4, expected[1] / 4, expected[2] / 4, 255]);
+            }
+        }
+
+        let allowed = (Self::MAX_MISMATCHED_FRACTION * (golden.width * golden.height) as f32) as usize;
+        if mismatched > allowed {
+            println!("\"{}\" has {} mismatched pixels, {} are allowed", self.scene.name(), mismatched, allowed);
+            let _ = image.save_png(&actual_path);
+            let diff = Image {
+                width: golden.width,
+                height: golden.height,
+                pixels: diff
+            };
+            let _ = diff.save_png(&diff_path);
+            1
+        } else {
+            println!("\"{}\" matches its golden image", self.scene.name());
+            0
+        }
+    }
+}
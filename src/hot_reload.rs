@@ -0,0 +1,95 @@
+//Dev-mode watcher that picks up edits to shaders and textures without restarting the game
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::assets::AssetResolver;
+use crate::render::TextureKeeper;
+use crate::shader::ShaderProgram;
+
+pub struct HotReloader {
+    directories: Vec<PathBuf>,
+    timestamps: HashMap<PathBuf, SystemTime>,
+    time_since_poll: f32
+}
+
+impl HotReloader {
+    const POLL_INTERVAL: f32 = 0.5;
+    const WATCHED_DIRECTORIES: [&'static str; 2] = ["shaders", "textures"];
+
+    pub fn new(resolver: &AssetResolver) -> Self {
+        let mut reloader = HotReloader {
+            directories: Self::WATCHED_DIRECTORIES.iter().map(|directory| PathBuf::from(resolver.resolve(directory))).collect(),
+            timestamps: HashMap::new(),
+            time_since_poll: 0.0
+        };
+
+        //Record the starting timestamps so that nothing counts as changed on the first poll
+        reloader.changed_files();
+        reloader
+    }
+
+    //Recompiles and re-uploads whatever changed on disk since the last poll
+    //Programs that fail to rebuild keep running their old code
+    //Returns true if anything was reloaded, in which case every uniform needs to be bound again
+    pub unsafe fn update(&mut self, delta_time: f32, programs: &mut [&mut ShaderProgram], texture_keeper: &mut TextureKeeper) -> bool {
+        self.time_since_poll += delta_time;
+        if self.time_since_poll < Self::POLL_INTERVAL { return false; }
+        self.time_since_poll = 0.0;
+
+        let mut reloaded = false;
+        for path in self.changed_files() {
+            for program in programs.iter_mut() {
+                if program.uses_file(&path) {
+                    match program.reload() {
+                        Ok(_) => {
+                            println!("Reloaded \"{}\"", program.description());
+                            reloaded = true;
+                        }
+                        Err(e) => { println!("Keeping the old program because {}", e); }
+                    }
+                }
+            }
+
+            if texture_keeper.reload_texture(&path.to_string_lossy()) {
+                println!("Reloaded \"{}\"", path.display());
+                reloaded = true;
+            }
+        }
+        reloaded
+    }
+
+    fn changed_files(&mut self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for directory in self.directories.iter() {
+            collect_files(directory, &mut files);
+        }
+
+        let mut changed = Vec::new();
+        for path in files {
+            let modified = match fs::metadata(&path).and_then(|metadata| metadata.modified()) {
+                Ok(m) => { m }
+                Err(_) => { continue; }
+            };
+            if let Some(old) = self.timestamps.insert(path.clone(), modified) {
+                if old != modified {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    if let Ok(entries) = fs::read_dir(directory) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                collect_files(&path, files);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+}
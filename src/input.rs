@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use crate::postprocess::PostEffect;
 
 pub type Input = (InputKind, glfw::Action);
 
@@ -22,9 +23,19 @@ pub enum Command {
     AppendToMenuChain(usize, usize),
     MenuChainRollback(usize),
     ToggleFullScreen,
+    Screenshot,
+    ToggleFollowCamera,
+    ToggleProjection,
+    ToggleMinimapMode,
+    CycleShadowQuality,
+    CycleShadowFilter,
     #[cfg(dev_tools)]
     ToggleCollisionVolumes,
-    ToggleBlur,
+    #[cfg(dev_tools)]
+    ToggleNormalView,
+    #[cfg(dev_tools)]
+    ToggleRenderStats,
+    TogglePostEffect(PostEffect),
     StartPlaying,
     ReturnToMainMenu,
     SpawnEnemy
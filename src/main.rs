@@ -2,24 +2,59 @@
 extern crate nalgebra_glm as glm;
 use std::{mem, ptr};
 use std::collections::HashMap;
-use std::fs::{File};
-use std::io::BufReader;
 use std::os::raw::c_void;
 use std::time::Instant;
 use glfw::{Action, Context, Key, MouseButton, WindowEvent, WindowMode};
 use gl::types::*;
 use glyph_brush::{ab_glyph::{FontArc, PxScale}, GlyphBrushBuilder, GlyphCruncher, Section, Text};
-use rodio::{Sink};
 use ozy_engine::{glutil, prims, routines};
 use ozy_engine::structs::OptionVec;
 use crate::structs::*;
+use crate::assets::{AssetError, AssetResolver};
+use crate::audio::{SoundEffect, SoundEffects};
+use crate::camera::{Camera, CameraMode, Projection};
+#[cfg(dev_tools)]
+use crate::hot_reload::HotReloader;
+use crate::capture::{FrameCapture, Image};
+use crate::collision::CollisionPlane;
+use crate::decals::{DecalRing, DecalStyle};
+use crate::gl_object::{LeakReport, VertexArray};
+use crate::golden::GoldenRun;
+use crate::minimap::{Minimap, MinimapBlip};
+use crate::music::{MusicCue, MusicManager};
+use crate::particles::{Emitter, ParticleSystem};
+use crate::postprocess::PostEffect;
 use crate::input::{Command, InputKind, {submit_input_command}};
 use crate::ui::{Menu, UIAnchor, UIState, UIText};
-use crate::render::{Bone, Framebuffer, InstancedMesh, RenderTarget, SimpleMesh, Skeleton, StaticGeometry, TextureKeeper};
-
+use crate::shader::ShaderProgram;
+use crate::render::{InstancedMesh, Material, SimpleMesh, Skeleton, StaticGeometry, TextureKeeper};
+use crate::renderer::{DrawItem, Renderer, Scene};
+
+mod archive;
+mod assets;
+mod audio;
+mod camera;
+mod capture;
+mod collision;
+mod decals;
+mod frustum;
+mod gl_object;
+mod golden;
+#[cfg(dev_tools)]
+mod hot_reload;
 mod input;
+mod minimap;
+mod music;
+mod particles;
+mod postprocess;
 mod render;
+mod renderer;
+mod rng;
+mod shader;
+mod shadow;
 mod structs;
+#[cfg(test)]
+mod test_util;
 mod ui;
 
 const DEFAULT_TEX_PARAMS: [(GLenum, GLenum); 4] = [
@@ -29,19 +64,8 @@ const DEFAULT_TEX_PARAMS: [(GLenum, GLenum); 4] = [
 	(gl::TEXTURE_MAG_FILTER, gl::LINEAR)
 ];
 
-//Binds each texture map specified in maps to each texture mapping unit in order
-unsafe fn bind_texture_maps(maps: &[GLuint]) {
-	for i in 0..maps.len() {
-		gl::ActiveTexture(gl::TEXTURE0 + i as GLenum);
-		gl::BindTexture(gl::TEXTURE_2D, maps[i]);
-	}
-}
-
-unsafe fn initialize_texture_samplers(program: GLuint, identifiers: &[&str]) {
-	for i in 0..identifiers.len() {
-		glutil::bind_int(program, identifiers[i], i as GLint);
-	}
-}
+//The names of the texture maps in shaders/mapped.frag, in texture unit order
+const TEXTURE_MAP_IDENTIFIERS: [&str; 6] = ["albedo_map", "normal_map", "roughness_map", "shadow_map", "ao_map", "metallic_map"];
 
 #[cfg(gloutput)]
 extern "system" fn gl_debug_callback(source: GLenum, gltype: GLenum, id: GLuint, severity: GLenum, length: GLsizei, message: *const GLchar, _: *mut c_void) {
@@ -98,28 +122,23 @@ extern "system" fn gl_debug_callback(source: GLenum, gltype: GLenum, id: GLuint,
 	println!("Message: {}", m);
 }
 
-unsafe fn draw_ui_elements(vao: GLuint, shader: GLuint, count: usize, clipping_from_screen: &glm::TMat4<f32>) {
-    gl::UseProgram(shader);
-	glutil::bind_matrix4(shader, "clipping_from_screen", &clipping_from_screen);
-	gl::BindVertexArray(vao);
-	gl::DrawElements(gl::TRIANGLES, 6 * count as GLint, gl::UNSIGNED_SHORT, ptr::null());
-}
-
 fn main() {
+	//Declared first so that it's dropped after everything else that holds GL objects
+	let _leak_report = LeakReport;
+
 	let mut is_fullscreen = false;
 	let game_title = "Whee! Tanks! for ipad";
 
-	//Initialize some constant transforms
-	let view_from_world = glm::mat4(-1.0, 0.0, 0.0, 0.0,
-									 0.0, 1.0, 0.0, 0.0,
-									 0.0, 0.0, 1.0, 0.0,
-									 0.0, 0.0, 0.0, 1.0) * glm::look_at(&glm::vec3(0.0, 1.5, -1.0), &glm::vec3(0.0, 0.0, 0.0), &glm::vec3(0.0, 1.0, 0.0));
-	let world_from_view = glm::affine_inverse(view_from_world);
-	let world_space_look_direction = world_from_view * glm::vec4(0.0, 0.0, 1.0, 0.0);
-
 	//Window resolution
 	const WINDOWED_SIZE: (u32, u32) = (1920, 1080);
 
+	//Golden-image runs render a scripted scene in a hidden window, compare it and then exit
+	let golden_run = GoldenRun::from_args();
+	let initial_size = if golden_run.is_some() { GoldenRun::RESOLUTION } else { WINDOWED_SIZE };
+
+	//Dumps every frame to disk at a fixed timestep when requested
+	let mut frame_capture = FrameCapture::from_args();
+
 	//Init glfw
 	let mut glfw = match glfw::init(glfw::FAIL_ON_ERRORS) {
 		Ok(g) => { g }
@@ -133,8 +152,13 @@ fn main() {
 	#[cfg(gloutput)]
 	glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(true));			//Debug context if we've compiled with renderer debugging
 
+	//Nothing needs to be seen during a golden-image run
+	if golden_run.is_some() {
+		glfw.window_hint(glfw::WindowHint::Visible(false));
+	}
+
 	//Create window
-    let (mut window, events) = glfw.create_window(WINDOWED_SIZE.0, WINDOWED_SIZE.1, game_title, WindowMode::Windowed).unwrap();
+    let (mut window, events) = glfw.create_window(initial_size.0, initial_size.1, game_title, WindowMode::Windowed).unwrap();
 
 	//Make the window non-resizable
 	window.set_resizable(false);
@@ -150,7 +174,7 @@ fn main() {
 	gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
 
 	//Struct of state that depends on screen size
-	let mut screen_state = ScreenState::new(WINDOWED_SIZE, &view_from_world);
+	let mut screen_state = ScreenState::new(initial_size);
 
 	//OpenGL static configuration
 	unsafe {
@@ -170,31 +194,14 @@ fn main() {
 		}
 	}
 
-	//Screen filling triangle with uvs chosen such that the uv range [0, 1] exactly covers the screen
-	let postprocessing_vao = unsafe {
-		let vs = [
-			-1.0, -1.0, 0.0, 0.0,
-			3.0, -1.0, 2.0, 0.0,
-			-1.0, 3.0, 0.0, 2.0
-		];
-		glutil::create_vertex_array_object(&vs, &[0, 1, 2], &[2, 2])
-	};
+	//Find out where the game's assets live
+	let asset_resolver = AssetResolver::from_environment();
 
-	//Compile shader programs
-	let mapped_shader = unsafe { glutil::compile_program_from_files("shaders/mapped.vert", "shaders/mapped.frag") };
-	let mapped_instanced_shader = unsafe { glutil::compile_program_from_files("shaders/mapped_instanced.vert", "shaders/mapped.frag") };
-	let shadow_shader = unsafe { glutil::compile_program_from_files("shaders/shadow.vert", "shaders/shadow.frag") };
-	let shadow_shader_instanced = unsafe { glutil::compile_program_from_files("shaders/shadow_instanced.vert", "shaders/shadow.frag") };
-	let glyph_shader = unsafe { glutil::compile_program_from_files("shaders/glyph.vert", "shaders/glyph.frag") };
-	let passthrough_shader = unsafe { glutil::compile_program_from_files("shaders/postprocessing.vert", "shaders/postprocessing.frag") };
-	let gaussian_shader = unsafe { glutil::compile_program_from_files("shaders/postprocessing.vert", "shaders/gaussian_blur.frag") };
-	let ui_shader = unsafe { glutil::compile_program_from_files("shaders/ui_button.vert", "shaders/ui_button.frag") };
-	let prim_shader = unsafe { glutil::compile_program_from_files("shaders/prim.vert", "shaders/prim.frag") };
-	let prim_instanced_shader = unsafe { glutil::compile_program_from_files("shaders/prim_instanced.vert", "shaders/prim.frag") };
-	let edit_shader = unsafe { glutil::compile_program_from_files("shaders/mapped.vert", "shaders/edit.frag") };
+	//Every asset that couldn't be loaded gets reported here instead of aborting, then summarised once loading is done
+	let mut missing_assets: Vec<AssetError> = Vec::new();
 
 	//Initialize texture caching data structure
-	let mut texture_keeper = TextureKeeper::new();
+	let mut texture_keeper = TextureKeeper::new(asset_resolver.clone());
 
 	//Array of the pieces of the map
 	let mut arena_pieces = Vec::new();
@@ -218,9 +225,8 @@ fn main() {
 		];
 
 		let piece = StaticGeometry {
-			vao: glutil::create_vertex_array_object(&vertices, &indices, &[3, 3, 3, 3, 2]),
-			albedo: texture_keeper.fetch_texture("bamboo_wood_semigloss", "albedo"),
-			normal: texture_keeper.fetch_texture("bamboo_wood_semigloss", "normal"),
+			vao: VertexArray::from_name(glutil::create_vertex_array_object(&vertices, &indices, &[3, 3, 3, 3, 2])),
+			material: Material::from_texture_set("bamboo_wood_semigloss", &mut texture_keeper),
 			model_matrix: glm::identity(),
 			index_count: indices.len() as GLsizei
 		};
@@ -228,68 +234,23 @@ fn main() {
 		arena_pieces.len() - 1
 	};
 
+	//Invisible walls along the edges of the floor that shells ricochet off of
+	let arena_walls = [
+		CollisionPlane::new(glm::vec4(1.0, 0.0, 0.0, 0.0), &glm::vec4(-floor_half_size.0, 0.0, 0.0, 1.0)),
+		CollisionPlane::new(glm::vec4(-1.0, 0.0, 0.0, 0.0), &glm::vec4(floor_half_size.0, 0.0, 0.0, 1.0)),
+		CollisionPlane::new(glm::vec4(0.0, 0.0, 1.0, 0.0), &glm::vec4(0.0, 0.0, -floor_half_size.1, 1.0)),
+		CollisionPlane::new(glm::vec4(0.0, 0.0, -1.0, 0.0), &glm::vec4(0.0, 0.0, floor_half_size.1, 1.0))
+	];
+
 	//Array of all tanks
 	let mut tanks: OptionVec<Tank> = OptionVec::new();
 
 	//Load the tank skeleton
-	let tank_skeleton = match routines::load_ozymesh("models/better_tank.ozy") {
-		Some(meshdata) => {
-			let mut node_list = Vec::with_capacity(meshdata.names.len());
-			let mut albedo_maps = Vec::with_capacity(meshdata.names.len());
-			let mut normal_maps = Vec::with_capacity(meshdata.names.len());
-			let mut roughness_maps = Vec::with_capacity(meshdata.names.len());
-			let mut bones = Vec::new();
-			let mut origins = vec![glm::zero(); 2];
-
-			//Load node info
-			for i in 0..meshdata.node_ids.len() {
-				let parent_id = meshdata.parent_ids[i] as usize;
-				let parent = if parent_id == 0 {
-					None
-				} else {
-					Some(parent_id - 1)
-				};
-
-				if meshdata.names[i] == "Turret" {
-					origins[Tank::TURRET_INDEX] = meshdata.origins[i];
-				}
-
-				if !node_list.contains(&(meshdata.node_ids[i] as usize - 1)) {
-					bones.push(Bone {
-						transform: glm::identity(),
-						parent
-					});
-				}
-				node_list.push(meshdata.node_ids[i] as usize - 1);
-
-				//Load albedo map
-				let albedo_id = unsafe { texture_keeper.fetch_texture(&meshdata.texture_names[i], "albedo") };
-				albedo_maps.push(albedo_id);
-
-				//Load normal map
-				let normal_id = unsafe { texture_keeper.fetch_texture(&meshdata.texture_names[i], "normal") };
-				normal_maps.push(normal_id);
-
-				//Load roughness map
-				let roughness_id = unsafe { texture_keeper.fetch_texture(&meshdata.texture_names[i], "roughness") };
-				roughness_maps.push(roughness_id);
-			}
-
-			//Create the vertex array object
-			let vao = unsafe { glutil::create_vertex_array_object(&meshdata.vertex_array.vertices, &meshdata.vertex_array.indices, &meshdata.vertex_array.attribute_offsets) };
-			Skeleton {
-				vao,
-				node_list,
-				geo_boundaries: meshdata.geo_boundaries,
-				albedo_maps,
-				normal_maps,
-				roughness_maps,
-				bones,
-				bone_origins: origins
-			}
-		}
-		None => {
-			panic!("Unable to load model.");
+	let tank_skeleton = match Skeleton::from_ozy(&asset_resolver.resolve("models/better_tank.ozy"), &mut texture_keeper, &Tank::REQUIRED_BONES) {
+		Ok(skeleton) => { skeleton }
+		Err(e) => {
+			missing_assets.push(e);
+			Skeleton::placeholder(&mut texture_keeper, &Tank::REQUIRED_BONES)
 		}
 	};
 	let mut player_tank_id = 0;
@@ -298,8 +259,27 @@ fn main() {
 	let mut shells: OptionVec<Shell> = OptionVec::new();
 
 	//Load shell graphics
-	let shell_mesh = SimpleMesh::from_ozy("models/real_shell.ozy", &mut texture_keeper);
-	let mut shell_instanced_mesh = InstancedMesh::new(shell_mesh.vao, shell_mesh.index_count, 1000, 5);
+	let shell_mesh = match SimpleMesh::from_ozy(&asset_resolver.resolve("models/real_shell.ozy"), &mut texture_keeper) {
+		Ok(mesh) => { mesh }
+		Err(e) => {
+			missing_assets.push(e);
+			SimpleMesh::placeholder(Shell::HIT_SPHERE_RADIUS, &mut texture_keeper)
+		}
+	};
+	let mut shell_instanced_mesh = InstancedMesh::new(shell_mesh.vao.name(), shell_mesh.index_count, 1000, 5);
+
+	//Compile shader programs
+	//Samplers are bound to texture units in the order they're listed, and the listed uniforms are checked to exist
+	let mut load_program = |vertex_path: &str, fragment_path: &str, samplers: &[&'static str], uniforms: &[&'static str]| {
+		let mut program = ShaderProgram::from_files(&asset_resolver.resolve(vertex_path), &asset_resolver.resolve(fragment_path), samplers, uniforms);
+		missing_assets.append(&mut program.take_problems());
+		program
+	};
+	let prim_shader = load_program("shaders/prim.vert", "shaders/prim.frag", &[], &["mvp", "color"]);
+	let edit_shader = load_program("shaders/mapped.vert", "shaders/edit.frag", &[], &[]);
+
+	//Owns the programs, shadow map and post-processing stack that each frame is drawn with
+	let mut renderer = unsafe { Renderer::new(&mut load_program, &mut texture_keeper, &tank_skeleton) };
 
 	//Set up the light source
 	let sun_direction = glm::normalize(&glm::vec4(1.0, 1.0, -1.0, 0.0));
@@ -307,65 +287,16 @@ fn main() {
 	//Frame timing data
 	let mut last_frame_instant = Instant::now();
 	let mut frame_count = 0;
-	let mut snapshot_frame = 0;	//The frame on which the cached 3D render will be re-drawn
 	let mut elapsed_time = 0.0;	//This only increments when the game is actually playing
 
-	let mut is_wireframe = false;
-
 	//Each frame this is filled with Commands, then drained when processed
 	let mut command_buffer = Vec::new();
 
-	//Initialize the shadow map
-	let shadow_size = 8192;
-	let shadow_rendertarget = unsafe {
-		let mut shadow_framebuffer = 0;
-		let mut shadow_texture = 0;
-
-		gl::GenFramebuffers(1, &mut shadow_framebuffer);
-		gl::GenTextures(1, &mut shadow_texture);
-
-		//Initialize the texture
-		gl::BindTexture(gl::TEXTURE_2D, shadow_texture);
-		gl::TexImage2D(
-			gl::TEXTURE_2D,
-			0,
-			gl::DEPTH_COMPONENT as GLint,
-			shadow_size,
-			shadow_size,
-			0,
-			gl::DEPTH_COMPONENT,
-			gl::FLOAT,
-			ptr::null()
-		);
-		glutil::apply_texture_parameters(&DEFAULT_TEX_PARAMS);
-
-		gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_framebuffer);
-		gl::FramebufferTexture2D(
-			gl::FRAMEBUFFER,
-			gl::DEPTH_ATTACHMENT,
-			gl::TEXTURE_2D,
-			shadow_texture,
-			0
-		);
-		gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+	//Each frame this is filled with SimulationEvents, then drained after the simulation step
+	let mut event_buffer = Vec::new();
 
-		let framebuffer = Framebuffer {
-			name: shadow_framebuffer,
-			size: (shadow_size, shadow_size),
-			clear_flags: gl::DEPTH_BUFFER_BIT,
-			cull_face: gl::FRONT
-		};
-
-		RenderTarget {
-			framebuffer,
-			texture: shadow_texture
-		}
-	};
-	let ortho_size = 5.0;
-	let shadow_matrix = glm::ortho(-ortho_size * 3.0, ortho_size * 3.0, -ortho_size * 3.0, ortho_size * 3.0, -ortho_size * 2.0, ortho_size * 3.0) * glm::mat4(-1.0, 0.0, 0.0, 0.0,
-										0.0, 1.0, 0.0, 0.0,
-										0.0, 0.0, 1.0, 0.0,
-										0.0, 0.0, 0.0, 1.0) * glm::look_at(&glm::vec4_to_vec3(&(sun_direction * 4.0)), &glm::zero(), &glm::vec3(0.0, 1.0, 0.0));
+	//The camera starts out looking down at the whole arena
+	let mut camera = Camera::new(floor_half_size, screen_state.aspect_ratio);
 
 
 	//Load font used for text rendering
@@ -377,7 +308,7 @@ fn main() {
 	let mut glyph_brush = GlyphBrushBuilder::using_font(font).build();
 
 	//Mouse state
-	let mut world_space_mouse = screen_state.world_from_clipping * glm::vec4(0.0, 0.0, 0.0, 1.0);
+	let mut clipping_space_mouse = glm::vec2(0.0, 0.0);
 	let mut screen_space_mouse = glm::vec2(0.0, 0.0);
 	let mut mouse_lbutton_pressed = false;
 	let mut mouse_rbutton_pressed = false;
@@ -388,11 +319,18 @@ fn main() {
 	let pause_menu_index = 1;
 	let settings_menu_index = 2;
 
+	//Hardcoded indices of the settings menu buttons whose labels show the current setting
+	let shadow_quality_button = 3;
+	let shadow_filter_button = 4;
+
 	//Hardcoded text indices
 	let title_text_index = 0;
+	#[cfg(dev_tools)]
+	let render_stats_text_index = 1;
 
+	let effects_menu_index = 3;
 	#[cfg(dev_tools)]
-	let dev_menu_index = 3;
+	let dev_menu_index = 4;
 
 	//Hardcoded menu chain indices
 	let main_chain_index;
@@ -436,6 +374,25 @@ fn main() {
 		let menu = Menu::new(
 			vec![
 				("Toggle fullscreen", Some(Command::ToggleFullScreen)),
+				("Toggle follow camera", Some(Command::ToggleFollowCamera)),
+				("Toggle perspective", Some(Command::ToggleProjection)),
+				("Cycle shadow quality", Some(Command::CycleShadowQuality)),
+				("Cycle shadow filtering", Some(Command::CycleShadowFilter)),
+				("Post-processing effects", Some(Command::AppendToMenuChain(main_chain_index, effects_menu_index))),
+				("Back", Some(Command::MenuChainRollback(main_chain_index))),
+			],
+			UIAnchor::DeadCenter
+		);
+		menus.push(menu);
+
+		//Post-processing effects menu data
+		let menu = Menu::new(
+			vec![
+				("Toggle bloom", Some(Command::TogglePostEffect(PostEffect::Bloom))),
+				("Toggle color grading", Some(Command::TogglePostEffect(PostEffect::ColorGrading))),
+				("Toggle vignette", Some(Command::TogglePostEffect(PostEffect::Vignette))),
+				("Toggle chromatic aberration", Some(Command::TogglePostEffect(PostEffect::ChromaticAberration))),
+				("Toggle FXAA", Some(Command::TogglePostEffect(PostEffect::Fxaa))),
 				("Back", Some(Command::MenuChainRollback(main_chain_index))),
 			],
 			UIAnchor::DeadCenter
@@ -448,8 +405,17 @@ fn main() {
 			let menu = Menu::new(
 				vec![
 					("Toggle wireframe", Some(Command::ToggleWireframe)),
-					("Toggle blur", Some(Command::ToggleBlur)),
+					("Toggle blur", Some(Command::TogglePostEffect(PostEffect::Blur))),
+					("Toggle monochrome", Some(Command::TogglePostEffect(PostEffect::Monochrome))),
+					("Toggle sepia", Some(Command::TogglePostEffect(PostEffect::Sepia))),
+					("Toggle bloom", Some(Command::TogglePostEffect(PostEffect::Bloom))),
+					("Toggle color grading", Some(Command::TogglePostEffect(PostEffect::ColorGrading))),
+					("Toggle vignette", Some(Command::TogglePostEffect(PostEffect::Vignette))),
+					("Toggle chromatic aberration", Some(Command::TogglePostEffect(PostEffect::ChromaticAberration))),
+					("Toggle FXAA", Some(Command::TogglePostEffect(PostEffect::Fxaa))),
 					("Toggle collision volume rendering", Some(Command::ToggleCollisionVolumes)),
+					("Toggle normal visualization", Some(Command::ToggleNormalView)),
+					("Toggle render stats", Some(Command::ToggleRenderStats)),
 					("Reset scenario", None)
 				],
 				UIAnchor::LeftAligned((20.0, 20.0))
@@ -459,10 +425,23 @@ fn main() {
 
 		//Title text
 		let title_text = UIText::new(game_title, 72.0, UIAnchor::CenterTop(40.0));
+		#[cfg(not(dev_tools))]
 		state.set_text_elements(vec![title_text]);
 
+		//Render stats under the minimap, filled in once they're turned on
+		#[cfg(dev_tools)]
+		{
+			let render_stats_text = UIText::new("Render stats", 24.0, UIAnchor::RightAligned((20.0, 280.0)));
+			state.set_text_elements(vec![title_text, render_stats_text]);
+		}
+
+		//Minimap in the top-right corner, only shown while there's a game going
+		state.minimap = Some(Minimap::new(UIAnchor::RightAligned((20.0, 20.0)), 240.0));
+
 		//Set the ui_state to use these menus
 		state.set_menus(menus);
+		state.set_menu_label(settings_menu_index, shadow_quality_button, renderer.shadow_map.quality.label());
+		state.set_menu_label(settings_menu_index, shadow_filter_button, renderer.shadow_filter.label());
 
 		state.append_to_chain(main_chain_index, main_menu_index);
 		state.toggle_text_element(title_text_index);
@@ -470,15 +449,26 @@ fn main() {
 	};
 
 	//Background music
-	let bgm_path = "music/dark_ruins.mp3";
-	let bgm_volume = 0.25;
-	let bgm_sink = match rodio::default_output_device() {
-		Some(device) => {
-			let sink = Sink::new(&device);
-			Some(sink)
-		}
-		None => { None }
-	};
+	//The main menu is silent for now
+	let music_volume = 0.25;
+	let mut music_manager = MusicManager::new(rodio::default_output_device(), music_volume);
+	music_manager.set_playlist(MusicCue::MainMenu, &[]);
+	music_manager.set_playlist(MusicCue::Battle, &[asset_resolver.resolve("music/dark_ruins.mp3").as_str()]);
+	music_manager.play_cue(MusicCue::MainMenu);
+
+	//Sound effects
+	let sfx_volume = 0.5;
+	let mut sound_effects = SoundEffects::new(rodio::default_output_device(), sfx_volume, &asset_resolver);
+
+	//Report everything that had to be replaced by a fallback
+	missing_assets.append(&mut texture_keeper.take_missing());
+	missing_assets.append(&mut sound_effects.take_missing());
+	missing_assets.append(&mut music_manager.take_missing());
+	assets::log_missing_assets(&missing_assets);
+
+	//Watches shaders/ and textures/ for edits
+	#[cfg(dev_tools)]
+	let mut hot_reloader = HotReloader::new(&asset_resolver);
 
 	//Initialize game state
 	let mut game_state = {
@@ -494,7 +484,9 @@ fn main() {
 			map.insert((InputKind::Key(Key::A), Action::Press), Command::RotatePlayerTank(-Tank::ROTATION_SPEED));
 			map.insert((InputKind::Key(Key::D), Action::Press), Command::RotatePlayerTank(Tank::ROTATION_SPEED));
 			map.insert((InputKind::Key(Key::Space), Action::Press), Command::SpawnEnemy);
+			map.insert((InputKind::Key(Key::Tab), Action::Press), Command::ToggleMinimapMode);
 			map.insert((InputKind::Mouse(MouseButton::Button1), Action::Press), Command::Fire);
+			map.insert((InputKind::Key(Key::F12), Action::Press), Command::Screenshot);
 			
 			#[cfg(dev_tools)]
 			map.insert((InputKind::Key(Key::GraveAccent), Action::Press), Command::ToggleMenu(dev_chain_index, dev_menu_index));
@@ -514,6 +506,7 @@ fn main() {
 			let mut map = HashMap::new();
 
 			map.insert((InputKind::Key(Key::Escape), Action::Press), Command::UnPauseGame);
+			map.insert((InputKind::Key(Key::F12), Action::Press), Command::Screenshot);
 
 			#[cfg(dev_tools)]
 			map.insert((InputKind::Key(Key::GraveAccent), Action::Press), Command::ToggleMenu(dev_chain_index, dev_menu_index));
@@ -522,21 +515,62 @@ fn main() {
 		};
 		input_maps.insert(GameStateKind::Paused, key_bindings);
 
+		//Main menu keybindings
+		let key_bindings = {
+			let mut map = HashMap::new();
+
+			map.insert((InputKind::Key(Key::F12), Action::Press), Command::Screenshot);
+
+			map
+		};
+		input_maps.insert(GameStateKind::MainMenu, key_bindings);
+
 		GameState::new(GameStateKind::MainMenu, input_maps)
 	};
 
-	//Effect to use during the postprocessing step
-	let mut image_effect = ImageEffect::None;
+
+	//Shows the renderer's draw call counts, refreshed once a second
+	#[cfg(dev_tools)]
+	let mut show_render_stats = false;
+	#[cfg(dev_tools)]
+	let mut render_stats_timer = 0.0;
 
 	//Hit sphere visualization data
 	let mut draw_collision = false;
-	let mut sphere_volume_instanced_mesh = InstancedMesh::new(prims::sphere_vao(1.0, 12, 12), prims::sphere_index_count(12, 12) as GLint, 1000, 2);
+	let sphere_volume_vao = unsafe { VertexArray::from_name(prims::sphere_vao(1.0, 12, 12)) };
+	let mut sphere_volume_instanced_mesh = InstancedMesh::new(sphere_volume_vao.name(), prims::sphere_index_count(12, 12) as GLint, 1000, 2);
+
+	//Particle effects
+	let mut particle_system = ParticleSystem::new(ParticleSystem::MAX_PARTICLES);
+	let particle_vao = unsafe { VertexArray::from_name(prims::sphere_vao(1.0, 6, 6)) };
+	let mut particle_instanced_mesh = InstancedMesh::new(particle_vao.name(), prims::sphere_index_count(6, 6) as GLint, ParticleSystem::MAX_PARTICLES, 2);
+	particle_instanced_mesh.enable_instance_colors(6);
+	let mut particle_transforms = Vec::with_capacity(ParticleSystem::MAX_PARTICLES * ParticleSystem::FLOATS_PER_TRANSFORM);
+	let mut particle_colors = Vec::with_capacity(ParticleSystem::MAX_PARTICLES * ParticleSystem::FLOATS_PER_COLOR);
+
+	//Scorch marks, shell impacts and tread tracks on the floor
+	let mut decal_ring = DecalRing::new(DecalRing::MAX_DECALS);
+	let decal_vao = unsafe {
+		let vertices = [
+			//Positions			//Uvs
+			-0.5, 0.0, -0.5,	0.0, 0.0,
+			0.5, 0.0, -0.5,		1.0, 0.0,
+			-0.5, 0.0, 0.5,		0.0, 1.0,
+			0.5, 0.0, 0.5,		1.0, 1.0
+		];
+		VertexArray::from_name(glutil::create_vertex_array_object(&vertices, &[0u16, 1, 2, 3, 2, 1], &[3, 2]))
+	};
+	let mut decal_instanced_mesh = InstancedMesh::new(decal_vao.name(), 6, DecalRing::MAX_DECALS, 2);
+	decal_instanced_mesh.enable_instance_colors(6);
+	let mut decal_transforms = Vec::with_capacity(DecalRing::MAX_DECALS * DecalRing::FLOATS_PER_TRANSFORM);
+	let mut decal_colors = Vec::with_capacity(DecalRing::MAX_DECALS * DecalRing::FLOATS_PER_COLOR);
+
+	//Ring on the floor under the player's tank so it always stands out, sharing the decals' quad
+	let mut ground_ring_instanced_mesh = InstancedMesh::new(decal_vao.name(), 6, 1, 2);
+	ground_ring_instanced_mesh.enable_instance_colors(6);
 
 	//Main loop
     while !window.should_close() {
-		//Per-frame flag
-		let use_cached_3D_render;
-
 		//Calculate time since the last frame started in seconds
 		let delta_time = {
 			let frame_instant = Instant::now();
@@ -545,6 +579,20 @@ fn main() {
 			dur.as_secs_f32()
 		};
 
+		//Golden-image runs step by a fixed amount and play back the scene's script so every run renders the same frames
+		//Frame captures also step by a fixed amount so that the image sequence plays back at the right speed
+		let delta_time = if let Some(run) = &golden_run {
+			command_buffer.extend(run.scene.commands(frame_count));
+			GoldenRun::FRAME_TIME
+		} else if let Some(recorder) = &frame_capture {
+			recorder.frame_time
+		} else {
+			delta_time
+		};
+
+		//Set when a screenshot is requested, then taken once the frame has been drawn
+		let mut take_screenshot = false;
+
 		//Handle window events
 		let key_bindings = game_state.get_input_map();				//Retrieve the input map to be used this frame based on the current gamestate
         for (_, event) in glfw::flush_messages(&events) {
@@ -570,42 +618,80 @@ fn main() {
 				WindowEvent::CursorPos(x, y) => {
 					screen_space_mouse = glm::vec2(x as f32, y as f32);
 					//We have to flip the y coordinate because glfw thinks (0, 0) is in the top left
-					let clipping_space_mouse = glm::vec4(x as f32 / (screen_state.window_size.0 as f32 / 2.0) - 1.0, -(y as f32 / (screen_state.window_size.1 as f32 / 2.0) - 1.0), 0.0, 1.0);
-					world_space_mouse = screen_state.world_from_clipping * clipping_space_mouse;
+					clipping_space_mouse = glm::vec2(x as f32 / (screen_state.window_size.0 as f32 / 2.0) - 1.0, -(y as f32 / (screen_state.window_size.1 as f32 / 2.0) - 1.0));
+				}
+				WindowEvent::Scroll(_, y) => {
+					if game_state.kind == GameStateKind::Playing {
+						camera.zoom_by(y as f32);
+					}
 				}
                 _ => {}
             }
 		}
 		
 		//Handle input from the UI buttons
-		ui_state.update_buttons(screen_space_mouse, mouse_lbutton_pressed, last_mouse_lbutton_pressed, &mut command_buffer);
+		if ui_state.update_buttons(screen_space_mouse, mouse_lbutton_pressed, last_mouse_lbutton_pressed, &mut command_buffer) {
+			sound_effects.play(SoundEffect::UIClick);
+		}
 		
 		//Process the generated commands
 		for command in command_buffer.drain(0..command_buffer.len()) {
 			match command {
 				Command::Quit => { window.set_should_close(true); }
-				Command::ToggleWireframe => { is_wireframe = !is_wireframe; }
+				Command::ToggleWireframe => { renderer.wireframe = !renderer.wireframe; }
 				#[cfg(dev_tools)]
 				Command::ToggleCollisionVolumes => { draw_collision = !draw_collision; }
-				Command::ToggleBlur => { 
-					image_effect = match image_effect {
-						ImageEffect::Blur => ImageEffect::None,
-						ImageEffect::None => ImageEffect::Blur
+				#[cfg(dev_tools)]
+				Command::ToggleNormalView => { renderer.visualize_normals = !renderer.visualize_normals; }
+				#[cfg(dev_tools)]
+				Command::ToggleRenderStats => {
+					show_render_stats = !show_render_stats;
+					if show_render_stats {
+						render_stats_timer = 1.0;
+					} else {
+						ui_state.hide_text_element(render_stats_text_index);
+					}
+				}
+				Command::TogglePostEffect(effect) => {
+					//Get a fresh 3D render this frame
+					renderer.request_fresh_render();
+
+					renderer.post_processor.toggle(effect);
+				}
+				Command::Screenshot => { take_screenshot = true; }
+				Command::ToggleMinimapMode => {
+					if let Some(minimap) = &mut ui_state.minimap {
+						minimap.toggle_mode();
 					}
-				 }
+				}
+				Command::ToggleFollowCamera => {
+					renderer.request_fresh_render();
+					camera.mode = match camera.mode {
+						CameraMode::Overhead => { CameraMode::Follow }
+						CameraMode::Follow => { CameraMode::Overhead }
+					};
+				}
+				Command::ToggleProjection => {
+					renderer.request_fresh_render();
+					camera.projection = match camera.projection {
+						Projection::Orthographic => { Projection::Perspective }
+						Projection::Perspective => { Projection::Orthographic }
+					};
+					camera.update(0.0, None, screen_state.aspect_ratio);
+				}
 				Command::ToggleFullScreen => {
 					//Get a fresh 3D render this frame
-					snapshot_frame = frame_count;
+					renderer.request_fresh_render();
 
 					if is_fullscreen {
-						screen_state = ScreenState::new(WINDOWED_SIZE, &view_from_world);
+						screen_state = ScreenState::new(WINDOWED_SIZE);
 						window.set_monitor(WindowMode::Windowed, 200, 200, screen_state.window_size.0, screen_state.window_size.1, Some(144));
 					} else {
 						glfw.with_primary_monitor_mut(|_, opt_monitor| {
 							if let Some(monitor) = opt_monitor {
 								let pos = monitor.get_pos();
 								if let Some(mode) = monitor.get_video_mode() {
-									screen_state = ScreenState::new((mode.width, mode.height), &view_from_world);
+									screen_state = ScreenState::new((mode.width, mode.height));
 									window.set_monitor(WindowMode::FullScreen(monitor), pos.0, pos.1, screen_state.window_size.0, screen_state.window_size.1, Some(144));
 								}
 							}
@@ -615,6 +701,17 @@ fn main() {
 
 					//Update the UI elements that depend on screen size
 					ui_state.resize(screen_state.window_size);
+					camera.update(0.0, None, screen_state.aspect_ratio);
+				}
+				Command::CycleShadowQuality => {
+					renderer.request_fresh_render();
+					unsafe { renderer.shadow_map.set_quality(renderer.shadow_map.quality.next()); }
+					ui_state.set_menu_label(settings_menu_index, shadow_quality_button, renderer.shadow_map.quality.label());
+				}
+				Command::CycleShadowFilter => {
+					renderer.request_fresh_render();
+					renderer.shadow_filter = renderer.shadow_filter.next();
+					ui_state.set_menu_label(settings_menu_index, shadow_filter_button, renderer.shadow_filter.label());
 				}
 				Command::ToggleMenu(chain, menu) => {
 					ui_state.toggle_menu(chain, menu);
@@ -631,7 +728,7 @@ fn main() {
 				}
 				Command::PauseGame => {
 					//Get a fresh 3D render this frame
-					snapshot_frame = frame_count;
+					renderer.request_fresh_render();
 
 					if let Some(tank) = tanks.get_mut_element(player_tank_id) {
 						tank.speed = 0.0;
@@ -643,10 +740,8 @@ fn main() {
 					ui_state.toggle_menu(main_chain_index, pause_menu_index);
 	
 					game_state.kind = GameStateKind::Paused;
-					image_effect = ImageEffect::Blur;
-					if let Some(sink) = &bgm_sink {
-						sink.set_volume(bgm_volume * 0.25);
-					}
+					renderer.post_processor.set_enabled(PostEffect::Blur, true);
+					music_manager.duck();
 				}
 				Command::UnPauseGame => {
 					//Hide pause menu
@@ -654,10 +749,8 @@ fn main() {
 					ui_state.toggle_menu(main_chain_index, pause_menu_index);
 
 					game_state.kind = GameStateKind::Playing;							
-					image_effect = ImageEffect::None;
-					if let Some(sink) = &bgm_sink {
-						sink.set_volume(bgm_volume);
-					}
+					renderer.post_processor.set_enabled(PostEffect::Blur, false);
+					music_manager.unduck();
 				}				
 				Command::Fire => {
 					if let Some(tank) = tanks.get_mut_element(player_tank_id) {
@@ -674,7 +767,7 @@ fn main() {
 					ui_state.reset();
 
 					game_state.kind = GameStateKind::Playing;
-					image_effect = ImageEffect::None;
+					renderer.post_processor.set_enabled(PostEffect::Blur, false);
 					elapsed_time = 0.0;
 
 					//Initialize the player's tank
@@ -692,32 +785,24 @@ fn main() {
 					tanks.insert(tank);
 
 					//Start the music
-					if let Some(sink) = &bgm_sink {
-						if sink.empty() {
-							match File::open(bgm_path) {
-								Ok(f) => { 
-									let source = rodio::Decoder::new(BufReader::new(f)).unwrap();
-									sink.append(source);
-									sink.set_volume(bgm_volume);
-								}
-								Err(e) => {	println!("Couldn't play \"{}\":\n{}", bgm_path, e); }
-							}
-						} else {
-							sink.play();
-							sink.set_volume(bgm_volume);
-						}
-					}
+					music_manager.play_cue(MusicCue::Battle);
+					music_manager.unduck();
 				}
 				Command::ReturnToMainMenu => {
 					//Get a fresh 3D render this frame
-					snapshot_frame = frame_count;
+					renderer.request_fresh_render();
 
 					//Reset game state
 					tanks.clear();
 					shells.clear();
+					particle_system.clear();
+					decal_ring.clear();
 
 					shell_instanced_mesh.update_buffer(&[]);
 					sphere_volume_instanced_mesh.update_buffer(&[]);
+					particle_instanced_mesh.update_buffer(&[]);
+					decal_instanced_mesh.update_buffer(&[]);
+					ground_ring_instanced_mesh.update_buffer(&[]);
 
 					//Reset UI state
 					ui_state.reset();
@@ -725,10 +810,10 @@ fn main() {
 					ui_state.toggle_menu(main_chain_index, main_menu_index);
 
 					game_state.kind = GameStateKind::MainMenu;
-					image_effect = ImageEffect::None;
-					if let Some(sink) = &bgm_sink {
-						sink.stop();
-					}
+					camera.reset(screen_state.aspect_ratio);
+					renderer.post_processor.set_enabled(PostEffect::Blur, false);
+					music_manager.play_cue(MusicCue::MainMenu);
+					music_manager.unduck();
 				}
 				Command::AppendToMenuChain(chain, dst) => {
 					ui_state.append_to_chain(chain, dst);
@@ -739,6 +824,30 @@ fn main() {
 			}
 		}
 
+		//Music keeps fading and looping regardless of game state
+		music_manager.update(delta_time);
+
+		//Refresh the renderer's workload on the overlay
+		//Showing it every time keeps it up after ui_state.reset() when the game starts or ends
+		#[cfg(dev_tools)]
+		if show_render_stats {
+			render_stats_timer += delta_time;
+			if render_stats_timer >= 1.0 {
+				render_stats_timer = 0.0;
+				let stats = renderer.stats;
+				ui_state.set_text(render_stats_text_index, format!("{} draw calls\n{} instances\n{} culled\n{} dropped", stats.draw_calls, stats.instances, stats.culled, stats.dropped));
+				ui_state.show_text_element(render_stats_text_index);
+			}
+		}
+
+		//Pick up edited shaders and textures, then force a fresh 3D render so that every uniform gets bound to the new programs
+		#[cfg(dev_tools)]
+		{
+			if unsafe { hot_reloader.update(delta_time, &mut renderer.programs_mut(), &mut texture_keeper) } {
+				renderer.request_fresh_render();
+			}
+		}
+
 		//-----------Simulating-----------
 		match game_state.kind {
 			GameStateKind::Playing => {
@@ -748,27 +857,61 @@ fn main() {
 				let mut hit_instance_buffer = Vec::with_capacity(buffer_size);
 
 				elapsed_time += delta_time;
-				use_cached_3D_render = false;
 
 				let player_origin = match &tanks[player_tank_id] {
 					Some(tank) => {
-						tank.bone_transforms[Tank::HULL_INDEX] * tank_skeleton.bone_origins[Tank::TURRET_INDEX]
+						tank.bone_transforms[tank.hull_bone] * tank.skeleton.bone_origins[tank.turret_bone]
 					}
 					None => {
 						glm::vec4(0.0, 0.0, 0.0, 1.0)
 					}
 				};
 
-				//Update the tanks
+				//Move the tanks
 				for j in 0..tanks.len() {
 					if let Some(tank) = tanks.get_mut_element(j) {
-						let aim_target;
-
 						//Update the tank's forward vector
 						tank.forward = glm::vec4_to_vec3(&(glm::rotation(tank.rotating * delta_time, &glm::vec3(0.0, 1.0, 0.0)) * glm::vec3_to_vec4(&tank.forward)));
 
 						//Update the tank's position
 						tank.position += tank.forward * tank.speed * delta_time;
+						tank.clamp_to_arena(floor_half_size);
+					}
+				}
+
+				//Push apart tanks whose hulls are overlapping
+				//The push is split by mass so that heavier tanks shove lighter ones out of the way
+				for i in 0..tanks.len() {
+					for j in i+1..tanks.len() {
+						let resolution = match (&tanks[i], &tanks[j]) {
+							(Some(a), Some(b)) => {
+								match collision::box_penetration(&a.hull_box(), &b.hull_box()) {
+									Some(push) => Some((push, a.mass, b.mass)),
+									None => None
+								}
+							}
+							_ => None
+						};
+
+						if let Some((push, mass_a, mass_b)) = resolution {
+							let total_mass = mass_a + mass_b;
+							//A shove never gets to push a tank off of the floor
+							if let Some(tank) = tanks.get_mut_element(i) {
+								tank.position += push * (mass_b / total_mass);
+								tank.clamp_to_arena(floor_half_size);
+							}
+							if let Some(tank) = tanks.get_mut_element(j) {
+								tank.position -= push * (mass_a / total_mass);
+								tank.clamp_to_arena(floor_half_size);
+							}
+						}
+					}
+				}
+
+				//Update the tanks
+				for j in 0..tanks.len() {
+					if let Some(tank) = tanks.get_mut_element(j) {
+						let aim_target;
 
 						tank.rotation = {
 							let new_x = -glm::cross(&tank.forward, &glm::vec3(0.0, 1.0, 0.0));
@@ -780,18 +923,41 @@ fn main() {
 							)
 						};
 
-						tank.bone_transforms[Tank::HULL_INDEX] = glm::translation(&tank.position) * tank.rotation;
+						//Kick the hull's nose up or down when the tank's speed changes, then let it settle
+						tank.pitch = tank.pitch * f32::exp(-delta_time / Tank::PITCH_SETTLE_TIME) - (tank.speed - tank.last_speed) * Tank::PITCH_PER_SPEED;
+						tank.last_speed = tank.speed;
+
+						//Scroll the treads by how far they've rolled this frame
+						//Turning left rolls the right tread forward and the left one back
+						let turning = tank.rotating * Tank::TREAD_HALF_WIDTH;
+						tank.tread_offsets[0] += (tank.speed - turning) * delta_time * Tank::TREAD_UV_PER_UNIT;
+						tank.tread_offsets[1] += (tank.speed + turning) * delta_time * Tank::TREAD_UV_PER_UNIT;
+
+						//Leave tracks behind every so often while the treads are rolling
+						tank.tread_mark_distance += (tank.speed.abs() + tank.rotating.abs() * Tank::TREAD_HALF_WIDTH) * delta_time;
+						if tank.tread_mark_distance >= DecalRing::TREAD_SPACING {
+							tank.tread_mark_distance -= DecalRing::TREAD_SPACING;
+							event_buffer.push(SimulationEvent::TreadMarks(tank.position, tank.forward));
+						}
+
+						//Ease the turret back from its recoil
+						tank.recoil = f32::max(0.0, tank.recoil - delta_time / Tank::RECOIL_DURATION);
+
+						//Pose the hull, then bring the world transforms up to date so the turret can be aimed from the hull's new position
+						tank.local_transforms[tank.hull_bone] = glm::translation(&tank.position) * tank.rotation * glm::rotation(tank.pitch, &glm::vec3(1.0, 0.0, 0.0));
+						tank.update_bone_transforms();
 						
 						match &mut tank.brain {
 							Brain::PlayerInput => {
-								//Simple ray-plane intersection.
+								//Intersect the ray under the mouse with the horizontal plane through the turret
+								let (ray_origin, ray_direction) = camera.ray_from_clipping(&clipping_space_mouse);
 								let plane_normal = glm::vec3(0.0, 1.0, 0.0);
-								let world_space_turret = tank.bone_transforms[Tank::HULL_INDEX] * tank_skeleton.bone_origins[Tank::TURRET_INDEX];
-								let t = glm::dot(&glm::vec4_to_vec3(&(world_space_turret - world_space_mouse)), &plane_normal) / glm::dot(&glm::vec4_to_vec3(&world_space_look_direction), &plane_normal);
-								let intersection = world_space_mouse + t * world_space_look_direction;
+								let world_space_turret = tank.bone_transforms[tank.hull_bone] * tank.skeleton.bone_origins[tank.turret_bone];
+								let t = glm::dot(&(glm::vec4_to_vec3(&world_space_turret) - ray_origin), &plane_normal) / glm::dot(&ray_direction, &plane_normal);
+								let intersection = ray_origin + t * ray_direction;
 
 								//Point the turret at the mouse cursor
-								aim_target = intersection;
+								aim_target = glm::vec4(intersection.x, intersection.y, intersection.z, 1.0);
 							}
 							Brain::DumbAI => {
 								//Point at player
@@ -803,17 +969,26 @@ fn main() {
 						}
 
 						//Point turret at aim_target
-						let world_space_turret = tank.bone_transforms[Tank::HULL_INDEX] * tank.skeleton.bone_origins[Tank::TURRET_INDEX];
+						let world_space_turret = tank.bone_transforms[tank.hull_bone] * tank.skeleton.bone_origins[tank.turret_bone];
 						tank.turret_forward = glm::normalize(&(aim_target - world_space_turret));
-						tank.bone_transforms[Tank::TURRET_INDEX] = {
+						let turret_world = {
 							let new_x = -glm::cross(&glm::vec4_to_vec3(&-tank.turret_forward), &glm::vec3(0.0, 1.0, 0.0));
-							tank.bone_transforms[Tank::HULL_INDEX] *
+							let recoil_offset = -tank.turret_forward * Tank::RECOIL_DISTANCE * tank.recoil * tank.recoil;
+
+							//The turret sits on the hull, so it tilts with the hull's pitch about the hull's side axis
+							let hull_side = glm::vec4_to_vec3(&(tank.rotation * glm::vec4(1.0, 0.0, 0.0, 0.0)));
+							glm::translation(&tank.position) *
+							glm::rotation(tank.pitch, &hull_side) *
+							glm::translation(&glm::vec4_to_vec3(&recoil_offset)) *
 							glm::mat4(new_x.x, 0.0, -tank.turret_forward.x, 0.0,
 									new_x.y, 1.0, -tank.turret_forward.y, 0.0,
 									new_x.z, 0.0, -tank.turret_forward.z, 0.0,
 									0.0, 0.0, 0.0, 1.0
-									) * glm::affine_inverse(tank.rotation)
+									)
 						};
+						let turret_bone = tank.turret_bone;
+						tank.set_bone_world_transform(turret_bone, &turret_world);
+						tank.update_bone_transforms();
 
 						//Fire a shell if the tank's firing flag is set and if the tank is not in cooldown
 						if tank.firing || mouse_rbutton_pressed {
@@ -827,25 +1002,30 @@ fn main() {
 							if (timer_expired && not_at_max_shells || turbo) && shell_buffer_has_room {
 								tank.last_shot_time = elapsed_time;
 								tank.live_shells += 1;
+								tank.recoil = 1.0;
 	
-								let transform = tank.bone_transforms[Tank::TURRET_INDEX];
+								let transform = tank.bone_transforms[tank.turret_bone];
 								let position = transform * glm::vec4(0.0, 0.0, 0.0, 1.0);
 								let velocity = tank.turret_forward * Shell::VELOCITY;
+
+								let muzzle = transform * tank.skeleton.bone_origins[tank.turret_bone] + tank.turret_forward * Tank::MUZZLE_LENGTH;
+								event_buffer.push(SimulationEvent::ShellFired(glm::vec4_to_vec3(&muzzle), glm::vec4_to_vec3(&tank.turret_forward)));
 	
 								shells.insert(Shell {
 									position,
 									velocity,
 									transform,
 									spawn_time: elapsed_time as f32,
-									shooter: j
+									shooter: j,
+									ricochets: 0
 								});
 							}
 							tank.firing = turbo;
 						}
 
 						//Add the tank's hit-sphere transform to the buffer
-						let hit_transform = tank.bone_transforms[Tank::TURRET_INDEX] *
-											glm::translation(&glm::vec4_to_vec3(&tank_skeleton.bone_origins[Tank::TURRET_INDEX])) *
+						let hit_transform = tank.bone_transforms[tank.turret_bone] *
+											glm::translation(&glm::vec4_to_vec3(&tank.skeleton.bone_origins[tank.turret_bone])) *
 											routines::uniform_scale(Tank::HIT_SPHERE_RADIUS);
 						for i in 0..floats_per_transform {
 							hit_instance_buffer.push(hit_transform[i]);
@@ -859,10 +1039,12 @@ fn main() {
 				//Update shells
 				let mut current_shell = 0;
 				let mut shell_transforms = vec![0.0; shells.count() * floats_per_transform];
+				let mut shell_hits = Vec::new();			//(time of impact, shell index, tank index)
 				for i in 0..shells.len() {
 					if let Some(shell) = shells.get_mut_element(i) {
 						//Check if the shell needs to be de-spawned
 						if elapsed_time > shell.spawn_time + Shell::LIFETIME {
+							event_buffer.push(SimulationEvent::ShellExpired(glm::vec4_to_vec3(&shell.position)));
 							if let Some(tank) = tanks.get_mut_element(shell.shooter) {
 								tank.live_shells -= 1;
 							}
@@ -870,8 +1052,82 @@ fn main() {
 							continue;
 						}
 
-						//Update position
-						shell.position += shell.velocity * delta_time;
+						//Offset from the shell's position to the center of its hit sphere
+						//This turns with the shell, so it's recomputed after every ricochet
+						let mut hit_offset = shell.transform * glm::vec3_to_vec4(&glm::vec4_to_vec3(&shell_mesh.origin));
+
+						//Sweep the shell's hit sphere along its path for this frame, ricocheting off of walls until the frame's time is used up
+						let mut remaining_time = delta_time;
+						let mut expired = false;
+						while remaining_time > 0.0 {
+							let start = shell.position + hit_offset;
+							let end = start + shell.velocity * remaining_time;
+
+							//Find the earliest wall impact
+							let mut wall_hit: Option<(f32, usize)> = None;
+							for k in 0..arena_walls.len() {
+								if let Some(t) = collision::sweep_sphere_plane(&start, &end, Shell::HIT_SPHERE_RADIUS, &arena_walls[k]) {
+									if wall_hit.map_or(true, |(best, _)| t < best) {
+										wall_hit = Some((t, k));
+									}
+								}
+							}
+
+							//Find the earliest tank impact
+							let mut tank_hit: Option<(f32, usize)> = None;
+							for sphere in hit_spheres.iter() {
+								if let CollisionEntity::Tank(tank_index) = sphere.target {
+									if let Some(t) = collision::sweep_sphere_sphere(&start, &end, Shell::HIT_SPHERE_RADIUS, sphere) {
+										if tank_hit.map_or(true, |(best, _)| t < best) {
+											tank_hit = Some((t, tank_index));
+										}
+									}
+								}
+							}
+
+							match (tank_hit, wall_hit) {
+								(Some((t, tank_index)), wall) if wall.map_or(true, |(wall_t, _)| t <= wall_t) => {
+									//Place the shell exactly at the point of impact and record the hit
+									shell.position += shell.velocity * remaining_time * t;
+									let time_of_impact = (delta_time - remaining_time * (1.0 - t)) / delta_time;
+									shell_hits.push((time_of_impact, i, tank_index));
+									break;
+								}
+								(_, Some((t, k))) => {
+									//Move the shell up to the wall
+									shell.position += shell.velocity * remaining_time * t;
+									remaining_time *= 1.0 - t;
+
+									if shell.ricochets == Shell::MAX_RICOCHETS {
+										expired = true;
+										break;
+									}
+									shell.ricochets += 1;
+									event_buffer.push(SimulationEvent::ShellRicochet(glm::vec4_to_vec3(&(shell.position + hit_offset))));
+
+									//Reflect the shell's velocity and turn the shell to face its new direction
+									let new_velocity = collision::reflect(&shell.velocity, &arena_walls[k].normal);
+									let angle = f32::atan2(new_velocity.x, new_velocity.z) - f32::atan2(shell.velocity.x, shell.velocity.z);
+									shell.transform = glm::rotation(angle, &glm::vec3(0.0, 1.0, 0.0)) * shell.transform;
+									shell.velocity = new_velocity;
+									hit_offset = shell.transform * glm::vec3_to_vec4(&glm::vec4_to_vec3(&shell_mesh.origin));
+								}
+								_ => {
+									shell.position += shell.velocity * remaining_time;
+									remaining_time = 0.0;
+								}
+							}
+						}
+
+						//Shells that have used up all of their ricochets are de-spawned
+						if expired {
+							event_buffer.push(SimulationEvent::ShellExpired(glm::vec4_to_vec3(&(shell.position + hit_offset))));
+							if let Some(tank) = tanks.get_mut_element(shell.shooter) {
+								tank.live_shells -= 1;
+							}
+							shells.delete(i);
+							continue;
+						}
 
 						//Update the translation part of the transform
 						shell.transform[12] = shell.position.x;
@@ -892,36 +1148,50 @@ fn main() {
 						current_shell += 1;
 					}
 				}
+
+				//Resolve shell-vs-tank hits in the order they happened during the frame
+				shell_hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+				for (_, shell_index, tank_index) in shell_hits {
+					if tanks[tank_index].is_none() {
+						continue;
+					}
+
+					if let Some(shell) = &shells[shell_index] {
+						if let Some(tank) = &tanks[tank_index] {
+							event_buffer.push(SimulationEvent::TankDestroyed(tank.position));
+						}
+						tanks.delete(tank_index);
+						if let Some(tank) = tanks.get_mut_element(shell.shooter) {
+							tank.live_shells -= 1;
+						}
+						shells.delete(shell_index);
+					}
+				}
 				
-				//Collision checking
+				//Shell-vs-shell collision checking
+				//Shell-vs-tank collisions were already handled by the sweep above
 				for i in 0..hit_spheres.len() {
 					for j in i+1..hit_spheres.len() {
-						let pos1 = &hit_spheres[i].origin;
-						let pos2 = &hit_spheres[j].origin;
-						let radius1 = &hit_spheres[i].radius;
-						let radius2 = &hit_spheres[j].radius;
-
-						let colliding = glm::distance(&pos1, &pos2) <= radius1 + radius2;
-						if colliding {
-							//Handle each collision case
-							let indices = [i, j];
-							for k in 0..indices.len() {
-								match hit_spheres[indices[k]].target {
-									CollisionEntity::Tank(index) => {
-										match hit_spheres[indices[k ^ 1]].target {
-											CollisionEntity::Shell(_) => {
-												tanks.delete(index);
-											}
-											_ => {}
-										}
-									}
-									CollisionEntity::Shell(index) => {
-										if let Some(shell) = &shells[index] {											
-											if let Some(tank) = tanks.get_mut_element(shell.shooter) {
-												tank.live_shells -= 1;
-											}
-											shells.delete(index);
+						if let (CollisionEntity::Shell(index1), CollisionEntity::Shell(index2)) = (&hit_spheres[i].target, &hit_spheres[j].target) {
+							let (index1, index2) = (*index1, *index2);
+							if shells[index1].is_none() || shells[index2].is_none() {
+								continue;
+							}
+
+							let pos1 = &hit_spheres[i].origin;
+							let pos2 = &hit_spheres[j].origin;
+							let radius1 = &hit_spheres[i].radius;
+							let radius2 = &hit_spheres[j].radius;
+
+							let colliding = glm::distance(&pos1, &pos2) <= radius1 + radius2;
+							if colliding {
+								event_buffer.push(SimulationEvent::ShellCollision(glm::vec4_to_vec3(&((pos1 + pos2) / 2.0))));
+								for index in [index1, index2].iter() {
+									if let Some(shell) = &shells[*index] {
+										if let Some(tank) = tanks.get_mut_element(shell.shooter) {
+											tank.live_shells -= 1;
 										}
+										shells.delete(*index);
 									}
 								}
 							}
@@ -934,196 +1204,150 @@ fn main() {
 
 				//Update GPU buffer storing hit volume transforms
 				sphere_volume_instanced_mesh.update_buffer(&hit_instance_buffer);
-			}
-			GameStateKind::MainMenu => { use_cached_3D_render = frame_count != snapshot_frame; }
-			GameStateKind::Paused => { use_cached_3D_render = frame_count != snapshot_frame; }
-		}
-		last_mouse_lbutton_pressed = mouse_lbutton_pressed;
-
-		//-----------CPU-side UI element rendering-----------
-		ui_state.synchronize();
 
-		//The names of the texture maps in shaders/mapped.frag
-		const TEXTURE_MAP_IDENTIFIERS: [&str; 4] = ["albedo_map", "normal_map", "roughness_map", "shadow_map"];
-
-		//Rendering
-		unsafe {
-			//Check if we're going to reuse last frame's 3D render
-			if !use_cached_3D_render {
-				//Enable depth testing for 3D scene drawing
-				gl::Enable(gl::DEPTH_TEST);
-
-				//Bind all uniforms that are constant per-frame
-				initialize_texture_samplers(mapped_shader, &TEXTURE_MAP_IDENTIFIERS);
-				glutil::bind_matrix4(mapped_shader, "shadow_matrix", &shadow_matrix);
-				glutil::bind_vector4(mapped_shader, "sun_direction", &sun_direction);
-				glutil::bind_matrix4(mapped_instanced_shader, "shadow_matrix", &shadow_matrix);
-				glutil::bind_matrix4(mapped_instanced_shader, "view_projection", &screen_state.clipping_from_world);
-				glutil::bind_vector4(mapped_instanced_shader, "sun_direction", &sun_direction);
-				initialize_texture_samplers(mapped_instanced_shader, &TEXTURE_MAP_IDENTIFIERS);
-				glutil::bind_matrix4(shadow_shader_instanced, "view_projection", &shadow_matrix);
-				initialize_texture_samplers(passthrough_shader, &["image_texture"]);
-				initialize_texture_samplers(gaussian_shader, &["image_texture"]);
-				initialize_texture_samplers(glyph_shader, &["glyph_texture"]);
-				glutil::bind_vector4(prim_instanced_shader, "color", &glm::vec4(0.0, 0.0, 1.0, 0.5));
-
-				//-----------Shadow map rendering-----------
-
-				//Bind shadowmap fbo
-				shadow_rendertarget.bind();
-
-				//Bind shadow program
-				gl::UseProgram(shadow_shader);
-
-				//Render arena pieces
-				for piece in arena_pieces.iter() {
-					gl::BindVertexArray(piece.vao);
-					glutil::bind_matrix4(shadow_shader, "mvp", &(shadow_matrix * piece.model_matrix));
-					gl::DrawElements(gl::TRIANGLES, piece.index_count, gl::UNSIGNED_SHORT, ptr::null());
-				}
-
-				//Render tanks
-				gl::BindVertexArray(tank_skeleton.vao);
-				for i in 0..tanks.len() {
-					if let Some(tank) = &tanks[i] {
-						for j in 0..tank.skeleton.node_list.len() {
-							let node_index = tank.skeleton.node_list[j];
-							glutil::bind_matrix4(shadow_shader, "mvp", &(shadow_matrix * tank.bone_transforms[node_index]));
-
-							tank.skeleton.draw_bone(j);
+				//React to what happened during this simulation step
+				for event in event_buffer.drain(0..event_buffer.len()) {
+					match event {
+						SimulationEvent::ShellFired(muzzle, direction) => {
+							particle_system.emit(&Emitter::MUZZLE_FLASH, &muzzle, &direction);
+							sound_effects.play_at(SoundEffect::Fire, &muzzle, &camera.position());
+						}
+						SimulationEvent::ShellRicochet(position) => {
+							particle_system.emit(&Emitter::SPARKS, &position, &glm::zero());
+							decal_ring.add(&DecalStyle::IMPACT, &position, None);
+							sound_effects.play_at(SoundEffect::Ricochet, &position, &camera.position());
+						}
+						SimulationEvent::ShellCollision(position) => {
+							particle_system.emit(&Emitter::SPARKS, &position, &glm::zero());
+							sound_effects.play_at(SoundEffect::ShellImpact, &position, &camera.position());
+							camera.add_trauma(Camera::SHELL_IMPACT_TRAUMA);
+						}
+						SimulationEvent::ShellExpired(position) => {
+							decal_ring.add(&DecalStyle::IMPACT, &position, None);
+						}
+						SimulationEvent::TankDestroyed(position) => {
+							particle_system.emit(&Emitter::FIREBALL, &position, &glm::vec3(0.0, 1.0, 0.0));
+							particle_system.emit(&Emitter::SMOKE, &position, &glm::vec3(0.0, 1.0, 0.0));
+							decal_ring.add(&DecalStyle::SCORCH, &position, None);
+							sound_effects.play_at(SoundEffect::TankDestroyed, &position, &camera.position());
+							camera.add_trauma(Camera::EXPLOSION_TRAUMA);
+						}
+						SimulationEvent::TreadMarks(position, forward) => {
+							decal_ring.add_tread_marks(&position, &forward, Tank::TREAD_HALF_WIDTH);
 						}
 					}
 				}
 
-				//Render shells
-				gl::UseProgram(shadow_shader_instanced);
-				shell_instanced_mesh.draw();
-
-				//-----------Main scene rendering-----------
+				//Update particles and their GPU buffers
+				particle_system.update(delta_time);
+				particle_system.fill_instance_buffers(&mut particle_transforms, &mut particle_colors);
+				particle_instanced_mesh.update_buffer(&particle_transforms);
+				particle_instanced_mesh.update_color_buffer(&particle_colors);
 
-				//Bind first ping-pong fbo
-				screen_state.ping_pong_fbos[0].bind();
-				
-				//Set polygon fill mode
-				if is_wireframe { gl::PolygonMode(gl::FRONT_AND_BACK, gl::LINE); }
-				else { gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL); }
+				//Age the decals and update their GPU buffers
+				decal_ring.update(delta_time);
+				decal_ring.fill_instance_buffers(&mut decal_transforms, &mut decal_colors);
+				decal_instanced_mesh.update_buffer(&decal_transforms);
+				decal_instanced_mesh.update_color_buffer(&decal_colors);
 
-				//Bind program for texture-mapped objects
-				gl::UseProgram(mapped_shader);
-				
-				//Bind the shadow map's data
-				gl::ActiveTexture(gl::TEXTURE3);
-				gl::BindTexture(gl::TEXTURE_2D, shadow_rendertarget.texture);
-
-				//Render static pieces of the arena
-				for piece in arena_pieces.iter() {
-					glutil::bind_matrix4(mapped_shader, "mvp", &(screen_state.clipping_from_world * piece.model_matrix));
-					glutil::bind_matrix4(mapped_shader, "model_matrix", &piece.model_matrix);
-					bind_texture_maps(&[piece.albedo, piece.normal]);
-
-					gl::BindVertexArray(piece.vao);
-					gl::DrawElements(gl::TRIANGLES, piece.index_count, gl::UNSIGNED_SHORT, ptr::null());
+				//Keep the ground ring under the player, in the player's team color
+				match &tanks[player_tank_id] {
+					Some(tank) => {
+						let transform = glm::translation(&glm::vec3(tank.position.x, 0.001, tank.position.z)) * routines::uniform_scale(Tank::GROUND_RING_RADIUS * 2.0);
+						ground_ring_instanced_mesh.update_buffer(transform.as_slice());
+						ground_ring_instanced_mesh.update_color_buffer(tank.tint.as_slice());
+					}
+					None => { ground_ring_instanced_mesh.update_buffer(&[]); }
 				}
+			}
+			GameStateKind::MainMenu | GameStateKind::Paused => {}
+		}
+
+		//The camera only moves while the game is being played so that cached renders stay valid
+		if game_state.kind == GameStateKind::Playing {
+			let player_position = tanks[player_tank_id].as_ref().map(|tank| tank.position);
+			camera.update(delta_time, player_position.as_ref(), screen_state.aspect_ratio);
+		}
+		last_mouse_lbutton_pressed = mouse_lbutton_pressed;
 
-				//Render the tanks
-				gl::BindVertexArray(tank_skeleton.vao);
+		//-----------CPU-side UI element rendering-----------
+		match game_state.kind {
+			GameStateKind::Playing | GameStateKind::Paused => {
+				let mut blips = Vec::with_capacity(tanks.count() + shells.count());
 				for i in 0..tanks.len() {
 					if let Some(tank) = &tanks[i] {
-						for j in 0..tank.skeleton.node_list.len() {
-							let node_index = tank.skeleton.node_list[j];
-							glutil::bind_matrix4(mapped_shader, "mvp", &(screen_state.clipping_from_world * tank.bone_transforms[node_index]));
-							glutil::bind_matrix4(mapped_shader, "model_matrix", &tank.bone_transforms[node_index]);
-							bind_texture_maps(&[tank.skeleton.albedo_maps[j], tank.skeleton.normal_maps[j], tank.skeleton.roughness_maps[j]]);
-			
-							tank.skeleton.draw_bone(j);
+						let color = [tank.tint.x, tank.tint.y, tank.tint.z, tank.tint.w];
+						if i == player_tank_id {
+							blips.push(MinimapBlip::PlayerTank(tank.position, color));
+						} else {
+							blips.push(MinimapBlip::EnemyTank(tank.position, color));
 						}
 					}
 				}
-
-				//Render tank shells
-				gl::UseProgram(mapped_instanced_shader);
-
-				//Bind the shell's texture maps
-				for i in 0..shell_mesh.texture_maps.len() {
-					gl::ActiveTexture(gl::TEXTURE0 + i as GLenum);
-					gl::BindTexture(gl::TEXTURE_2D, shell_mesh.texture_maps[i]);
-				}
-
-				//Draw shells
-				shell_instanced_mesh.draw();
-
-				//Render collision volumes
-				#[cfg(dev_tools)]
-				{
-					if draw_collision {
-						gl::UseProgram(prim_instanced_shader);
-						glutil::bind_matrix4(prim_instanced_shader, "view_projection", &screen_state.clipping_from_world);
-						sphere_volume_instanced_mesh.draw();
+				for i in 0..shells.len() {
+					if let Some(shell) = &shells[i] {
+						blips.push(MinimapBlip::Shell(glm::vec4_to_vec3(&shell.position)));
 					}
 				}
-
-				//-----------Apply post-processing effects-----------
-				gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);			//Disable wireframe rendering for this section if it was enabled
-				gl::BindVertexArray(postprocessing_vao);				//Bind the VAO that just defines a screen-filling triangle
-				gl::ActiveTexture(gl::TEXTURE0);
-
-				//Apply the active image effect
-				match image_effect {
-					ImageEffect::Blur => {
-						let passes = 4;
-		
-						gl::UseProgram(gaussian_shader);
-						for _ in 0..passes {
-							//Do a horizontal pass followed by a vertical one.
-							for i in 0..screen_state.ping_pong_fbos.len() {
-								screen_state.ping_pong_fbos[i ^ 1].bind();
-								gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[i].texture);							
-								gl::GenerateMipmap(gl::TEXTURE_2D);											//Gen mipmaps so we can source from the downscaled image
-								glutil::bind_int(gaussian_shader, "horizontal", i as GLint ^ 1);			//Flag if this is a horizontal or vertical blur pass
-								gl::DrawElements(gl::TRIANGLES, 3, gl::UNSIGNED_SHORT, ptr::null());
-							}
-						}
-		
-						//Render result to the default framebuffer
-						screen_state.default_framebuffer.bind();
-						gl::UseProgram(passthrough_shader);
-						gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[0].texture);
-						gl::DrawElements(gl::TRIANGLES, 3, gl::UNSIGNED_SHORT, ptr::null());
-					}
-					ImageEffect::None => {
-						//Run the render through the passthrough shader
-						screen_state.default_framebuffer.bind();
-						gl::UseProgram(passthrough_shader);
-						gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[0].texture);
-						gl::DrawElements(gl::TRIANGLES, 3, gl::UNSIGNED_SHORT, ptr::null());
-					}
+				ui_state.update_minimap(floor_half_size, &blips);
+			}
+			GameStateKind::MainMenu => {
+				if let Some(minimap) = &mut ui_state.minimap {
+					minimap.clear();
 				}
-			} else {
-				gl::PolygonMode(gl::FRONT_AND_BACK, gl::FILL);			//Disable wireframe rendering for this section if it was enabled
-				gl::BindVertexArray(postprocessing_vao);				//Bind the VAO that just defines a screen-filling triangle
-				gl::ActiveTexture(gl::TEXTURE0);
-
-				//Run the cached render through the passthrough shader
-				screen_state.default_framebuffer.bind();
-				gl::UseProgram(passthrough_shader);
-				gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[0].texture);
-				gl::DrawElements(gl::TRIANGLES, 3, gl::UNSIGNED_SHORT, ptr::null());
 			}
+		}
+		ui_state.synchronize();
 
-			//Before rendering 2D elements
-			gl::Disable(gl::DEPTH_TEST);			//Disable depth testing
-
-			//Render UI buttons
-			if let Some(vao) = ui_state.buttons_vao {
-				draw_ui_elements(vao, ui_shader, ui_state.button_count(), &screen_state.clipping_from_screen);
+		//Queue up everything in the world that gets drawn
+		let mut draw_items = Vec::with_capacity(arena_pieces.len() + tanks.count() + 5);
+		for piece in arena_pieces.iter() {
+			draw_items.push(DrawItem::Static(piece));
+		}
+		draw_items.push(DrawItem::Decals(&decal_instanced_mesh));
+		draw_items.push(DrawItem::GroundRings(&ground_ring_instanced_mesh));
+		for i in 0..tanks.len() {
+			if let Some(tank) = &tanks[i] {
+				draw_items.push(DrawItem::Tank(tank));
 			}
+		}
+		draw_items.push(DrawItem::Instanced(&shell_instanced_mesh, &shell_mesh.material));
+		draw_items.push(DrawItem::Particles(&particle_instanced_mesh));
+		#[cfg(dev_tools)]
+		{
+			if draw_collision {
+				draw_items.push(DrawItem::CollisionVolumes(&sphere_volume_instanced_mesh));
+			}
+		}
+		let scene = Scene {
+			camera: &camera,
+			sun_direction,
+			arena_half_size: floor_half_size,
+			items: draw_items
+		};
 
-			//Render text
-			if let Some(vao) = ui_state.glyph_vao {
-				gl::ActiveTexture(gl::TEXTURE0);
-				gl::BindTexture(gl::TEXTURE_2D, ui_state.glyph_texture);
+		//Rendering
+		unsafe {
+			//The 3D render only changes while the game is being played, so otherwise the last one is reused
+			renderer.render(&scene, &screen_state, &ui_state, game_state.kind != GameStateKind::Playing);
+
+			//Read back the finished frame, UI and all
+			if take_screenshot || frame_capture.is_some() {
+				let image = Image::from_default_framebuffer(screen_state.window_size);
+				if take_screenshot {
+					capture::save_screenshot(&image);
+				}
+				if let Some(recorder) = &mut frame_capture {
+					recorder.save(&image);
+				}
+			}
 
-				draw_ui_elements(vao, glyph_shader, ui_state.glyph_count, &screen_state.clipping_from_screen);
+			//Compare the 3D render with its golden image once the scene's script has played out
+			if let Some(run) = &golden_run {
+				if frame_count == run.scene.capture_frame() {
+					let image = Image::from_texture(renderer.post_processor.output_texture(&screen_state));
+					std::process::exit(run.finish(&image));
+				}
 			}
 		}
 
@@ -0,0 +1,173 @@
+//Overhead view of the arena drawn into a corner of the screen
+//It's built out of flat-colored quads in screen space, so it draws with the same program as the UI buttons
+use gl::types::*;
+use ozy_engine::glutil;
+use std::{mem, ptr};
+use std::os::raw::c_void;
+use crate::gl_object::VertexArray;
+use crate::ui::UIAnchor;
+
+//Something in the arena that shows up on the minimap
+pub enum MinimapBlip {
+    PlayerTank(glm::TVec3<f32>, [f32; 4]),         //Position and team color
+    EnemyTank(glm::TVec3<f32>, [f32; 4]),
+    Shell(glm::TVec3<f32>)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MinimapMode {
+    Arena,              //The whole arena, its walls, every tank and every shell
+    Radar(f32)          //Centered on the player, only showing enemies within this many world units
+}
+
+pub struct Minimap {
+    pub anchor: UIAnchor,
+    pub size: f32,                              //Width and height in pixels
+    pub mode: MinimapMode,
+    pub vao: VertexArray,                       //Sized for MAX_QUADS up front, only the vertices change between updates
+    vertex_buffer: GLuint,                      //Owned by vao
+    quad_count: usize,
+    vertices: Vec<f32>
+}
+
+impl Minimap {
+    const MAX_QUADS: usize = (u16::MAX as usize + 1) / 4;       //So that every vertex can be reached with a u16 index
+    const FLOATS_PER_VERTEX: usize = 6;                         //Screen-space position then RGBA color
+    const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+    const FLOOR_COLOR: [f32; 4] = [0.35, 0.3, 0.2, 0.6];
+    const WALL_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 0.8];
+    const SHELL_COLOR: [f32; 4] = [1.0, 0.9, 0.4, 1.0];
+    const MARGIN: f32 = 6.0;                    //Pixels between the edge of the widget and the arena
+    const WALL_THICKNESS: f32 = 2.0;
+    const TANK_SIZE: f32 = 8.0;
+    const SHELL_SIZE: f32 = 3.0;
+    pub const DEFAULT_RADAR_RANGE: f32 = 6.0;
+
+    pub fn new(anchor: UIAnchor, size: f32) -> Self {
+        //Every quad is wound the same way as the UI buttons, so the index buffer never changes
+        let mut indices = Vec::with_capacity(Self::MAX_QUADS * 6);
+        for i in 0..Self::MAX_QUADS {
+            let first = (i * 4) as u16;
+            indices.extend_from_slice(&[first, first + 1, first + 2, first + 3, first + 2, first + 1]);
+        }
+
+        let (vao, vertex_buffer) = unsafe {
+            let vao = VertexArray::from_name(glutil::create_vertex_array_object(&[0.0; 4 * Self::FLOATS_PER_VERTEX], &indices, &[2, 4]));
+
+            //from_name() leaves the vao bound, so attribute 0 still points at the vertex buffer it was made with
+            //Its storage gets replaced with room for every quad, marked as dynamic since it's rewritten each update
+            let mut vertex_buffer = 0;
+            gl::GetVertexAttribiv(0, gl::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING, &mut vertex_buffer);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer as GLuint);
+            gl::BufferData(gl::ARRAY_BUFFER, (Self::MAX_QUADS * 4 * Self::FLOATS_PER_VERTEX * mem::size_of::<GLfloat>()) as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+            (vao, vertex_buffer as GLuint)
+        };
+
+        Minimap {
+            anchor,
+            size,
+            mode: MinimapMode::Arena,
+            vao,
+            vertex_buffer,
+            quad_count: 0,
+            vertices: Vec::new()
+        }
+    }
+
+    pub fn quad_count(&self) -> usize { self.quad_count }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            MinimapMode::Arena => { MinimapMode::Radar(Self::DEFAULT_RADAR_RANGE) }
+            MinimapMode::Radar(_) => { MinimapMode::Arena }
+        };
+    }
+
+    //Hides the minimap until the next update()
+    pub fn clear(&mut self) {
+        self.quad_count = 0;
+    }
+
+    //Rebuilds the minimap's geometry from the current simulation state
+    //arena_half_size is the half size of the floor in x and z
+    pub fn update(&mut self, window_size: (u32, u32), arena_half_size: (f32, f32), blips: &[MinimapBlip]) {
+        self.vertices.clear();
+        self.quad_count = 0;
+
+        let (left, top) = self.anchor.place((self.size, self.size), window_size);
+        let center = glm::vec2(left + self.size / 2.0, top + self.size / 2.0);
+        let inner_size = self.size - Self::MARGIN * 2.0;
+        self.push_quad(&center, &glm::vec2(self.size, self.size), Self::BACKGROUND_COLOR);
+
+        match self.mode {
+            MinimapMode::Arena => {
+                //World x maps to screen x and world z maps to screen y, keeping the arena's aspect ratio
+                //Screen y grows downward, so z is flipped to keep +z at the top
+                let pixels_per_unit = inner_size / (2.0 * f32::max(arena_half_size.0, arena_half_size.1));
+                let to_screen = |position: &glm::TVec3<f32>| center + glm::vec2(position.x, -position.z) * pixels_per_unit;
+
+                let floor_size = glm::vec2(arena_half_size.0, arena_half_size.1) * 2.0 * pixels_per_unit;
+                self.push_quad(&center, &floor_size, Self::FLOOR_COLOR);
+                for (offset, size) in [
+                    (glm::vec2(-floor_size.x / 2.0, 0.0), glm::vec2(Self::WALL_THICKNESS, floor_size.y)),
+                    (glm::vec2(floor_size.x / 2.0, 0.0), glm::vec2(Self::WALL_THICKNESS, floor_size.y)),
+                    (glm::vec2(0.0, -floor_size.y / 2.0), glm::vec2(floor_size.x, Self::WALL_THICKNESS)),
+                    (glm::vec2(0.0, floor_size.y / 2.0), glm::vec2(floor_size.x, Self::WALL_THICKNESS))
+                ].iter() {
+                    self.push_quad(&(center + offset), size, Self::WALL_COLOR);
+                }
+
+                for blip in blips.iter() {
+                    match blip {
+                        MinimapBlip::PlayerTank(position, color) | MinimapBlip::EnemyTank(position, color) => {
+                            self.push_quad(&to_screen(position), &glm::vec2(Self::TANK_SIZE, Self::TANK_SIZE), *color);
+                        }
+                        MinimapBlip::Shell(position) => {
+                            self.push_quad(&to_screen(position), &glm::vec2(Self::SHELL_SIZE, Self::SHELL_SIZE), Self::SHELL_COLOR);
+                        }
+                    }
+                }
+            }
+            MinimapMode::Radar(range) => {
+                //Nothing to center on without a player
+                let player = blips.iter().find_map(|blip| match blip {
+                    MinimapBlip::PlayerTank(position, color) => { Some((*position, *color)) }
+                    _ => { None }
+                });
+
+                if let Some((player_position, player_color)) = player {
+                    let pixels_per_unit = inner_size / (2.0 * range);
+                    self.push_quad(&center, &glm::vec2(Self::TANK_SIZE, Self::TANK_SIZE), player_color);
+
+                    for blip in blips.iter() {
+                        if let MinimapBlip::EnemyTank(position, color) = blip {
+                            let offset = glm::vec2(position.x - player_position.x, player_position.z - position.z);
+                            if glm::length(&offset) <= range {
+                                self.push_quad(&(center + offset * pixels_per_unit), &glm::vec2(Self::TANK_SIZE, Self::TANK_SIZE), *color);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.vertices.len() > 0 {
+            unsafe {
+                gl::BindBuffer(gl::ARRAY_BUFFER, self.vertex_buffer);
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, (self.vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, &self.vertices[0] as *const GLfloat as *const c_void);
+            }
+        }
+    }
+
+    //Adds an axis-aligned quad with the given center and size in pixels
+    fn push_quad(&mut self, center: &glm::TVec2<f32>, size: &glm::TVec2<f32>, color: [f32; 4]) {
+        if self.quad_count == Self::MAX_QUADS { return; }
+
+        let (min, max) = (center - size / 2.0, center + size / 2.0);
+        for corner in [(min.x, min.y), (min.x, max.y), (max.x, min.y), (max.x, max.y)].iter() {
+            self.vertices.extend_from_slice(&[corner.0, corner.1]);
+            self.vertices.extend_from_slice(&color);
+        }
+        self.quad_count += 1;
+    }
+}
@@ -0,0 +1,190 @@
+use rodio::{Decoder, Device, Sink, Source};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::mem;
+use std::path::Path;
+use crate::assets::AssetError;
+
+//Which part of the game the music is accompanying
+//Each cue has its own playlist
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum MusicCue {
+    MainMenu,
+    Battle
+}
+
+struct Track {
+    sink: Sink,
+    fade: f32,                  //0.0 is silent and 1.0 is full volume
+    elapsed: f32,
+    fade_out_at: Option<f32>    //Seconds in at which to start crossfading into the next track, if the track's length is known
+}
+
+impl Track {
+    fn nearly_over(&self) -> bool {
+        match self.fade_out_at {
+            Some(time) => { self.elapsed >= time }
+            None => { false }
+        }
+    }
+}
+
+//Owns the background music
+//Playlists loop, switching cues crossfades between them, and ducking lowers the volume while something else needs attention
+pub struct MusicManager {
+    device: Option<Device>,
+    playlists: HashMap<MusicCue, Vec<String>>,
+    cue: Option<MusicCue>,
+    track_index: usize,
+    current: Option<Track>,
+    fading_out: Vec<Track>,
+    volume: f32,
+    duck: f32,
+    duck_target: f32,
+    missing: Vec<AssetError>
+}
+
+impl MusicManager {
+    const CROSSFADE_TIME: f32 = 2.0;
+    const DUCK_LEVEL: f32 = 0.25;
+    const DUCK_TIME: f32 = 0.3;
+
+    pub fn new(device: Option<Device>, volume: f32) -> Self {
+        MusicManager {
+            device,
+            playlists: HashMap::new(),
+            cue: None,
+            track_index: 0,
+            current: None,
+            fading_out: Vec::new(),
+            volume,
+            duck: 1.0,
+            duck_target: 1.0,
+            missing: Vec::new()
+        }
+    }
+
+    //Tracks that don't exist are left out of the playlist
+    pub fn set_playlist(&mut self, cue: MusicCue, paths: &[&str]) {
+        let mut playlist = Vec::with_capacity(paths.len());
+        for path in paths.iter() {
+            if Path::new(path).exists() {
+                playlist.push(path.to_string());
+            } else {
+                self.missing.push(AssetError::Missing(path.to_string()));
+            }
+        }
+        self.playlists.insert(cue, playlist);
+    }
+
+    //Hands over every track that has been found missing since the last call
+    pub fn take_missing(&mut self) -> Vec<AssetError> {
+        mem::take(&mut self.missing)
+    }
+
+    //Crossfades from whatever is playing to the start of cue's playlist
+    //Does nothing if cue is already playing
+    pub fn play_cue(&mut self, cue: MusicCue) {
+        if self.cue == Some(cue) { return; }
+
+        self.cue = Some(cue);
+        self.track_index = 0;
+        if let Some(track) = self.current.take() {
+            self.fading_out.push(track);
+        }
+        self.current = self.start_track();
+    }
+
+    pub fn duck(&mut self) { self.duck_target = Self::DUCK_LEVEL; }
+
+    pub fn unduck(&mut self) { self.duck_target = 1.0; }
+
+    //Call this once per frame to advance fades and the playlist
+    pub fn update(&mut self, delta_time: f32) {
+        //Move the duck level towards its target
+        let duck_step = delta_time * (1.0 - Self::DUCK_LEVEL) / Self::DUCK_TIME;
+        if self.duck < self.duck_target {
+            self.duck = f32::min(self.duck_target, self.duck + duck_step);
+        } else {
+            self.duck = f32::max(self.duck_target, self.duck - duck_step);
+        }
+
+        //Crossfade into the next track in the playlist as the current one nears its end
+        //Tracks whose length can't be known just fade the next one in once they finish
+        let next_due = match &mut self.current {
+            Some(track) => {
+                track.elapsed += delta_time;
+                track.nearly_over() || track.sink.empty()
+            }
+            None => { false }
+        };
+        if next_due {
+            if let Some(track) = self.current.take() {
+                if !track.sink.empty() {
+                    self.fading_out.push(track);
+                }
+            }
+
+            //The playlist may have been emptied since this track started
+            let playlist_length = match self.cue.and_then(|cue| self.playlists.get(&cue)) {
+                Some(playlist) => { playlist.len() }
+                None => { 0 }
+            };
+            if playlist_length > 0 {
+                self.track_index = (self.track_index + 1) % playlist_length;
+                self.current = self.start_track();
+            }
+        }
+
+        let fade_step = delta_time / Self::CROSSFADE_TIME;
+        let volume = self.volume * self.duck;
+        if let Some(track) = &mut self.current {
+            track.fade = f32::min(1.0, track.fade + fade_step);
+            track.sink.set_volume(volume * track.fade);
+        }
+        for track in self.fading_out.iter_mut() {
+            track.fade = f32::max(0.0, track.fade - fade_step);
+            track.sink.set_volume(volume * track.fade);
+        }
+
+        //Dropping a Sink stops its playback
+        self.fading_out.retain(|track| track.fade > 0.0);
+    }
+
+    fn start_track(&self) -> Option<Track> {
+        let device = self.device.as_ref()?;
+        let path = self.playlists.get(&self.cue?)?.get(self.track_index)?;
+
+        match File::open(path) {
+            Ok(f) => {
+                match Decoder::new(BufReader::new(f)) {
+                    Ok(source) => {
+                        //Short tracks spend at most half their length fading out
+                        let fade_out_at = source.total_duration().map(|length| {
+                            let length = length.as_secs_f32();
+                            length - f32::min(Self::CROSSFADE_TIME, length * 0.5)
+                        });
+                        let sink = Sink::new(device);
+                        sink.set_volume(0.0);
+                        sink.append(source);
+                        Some(Track {
+                            sink,
+                            fade: 0.0,
+                            elapsed: 0.0,
+                            fade_out_at
+                        })
+                    }
+                    Err(e) => {
+                        println!("{}", AssetError::Undecodable(path.clone(), e.to_string()));
+                        None
+                    }
+                }
+            }
+            Err(_) => {
+                println!("{}", AssetError::from_path(path));
+                None
+            }
+        }
+    }
+}
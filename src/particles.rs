@@ -0,0 +1,350 @@
+//CPU-side particle simulation
+//Nothing in here touches OpenGL, the results are copied into an InstancedMesh for drawing
+use crate::rng::Rng;
+
+//Describes what the particles spawned by a single burst look like and how they move
+pub struct Emitter {
+    pub count: usize,
+    pub speed: (f32, f32),              //Range of initial speeds
+    pub spread: f32,                    //How far particles stray from the emit direction. Zero is a straight line, values above one are close to uniform
+    pub lifetime: (f32, f32),           //Range of lifetimes in seconds
+    pub size: (f32, f32),               //Radius at birth and at death
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub gravity: f32,                   //Downward acceleration, negative values make particles rise
+    pub drag: f32
+}
+
+impl Emitter {
+    pub const MUZZLE_FLASH: Emitter = Emitter {
+        count: 12,
+        speed: (1.0, 3.0),
+        spread: 0.3,
+        lifetime: (0.05, 0.15),
+        size: (0.08, 0.02),
+        start_color: [1.0, 0.9, 0.5, 1.0],
+        end_color: [1.0, 0.4, 0.0, 0.0],
+        gravity: 0.0,
+        drag: 8.0
+    };
+
+    pub const SPARKS: Emitter = Emitter {
+        count: 16,
+        speed: (2.0, 5.0),
+        spread: 2.0,
+        lifetime: (0.2, 0.5),
+        size: (0.03, 0.01),
+        start_color: [1.0, 0.85, 0.4, 1.0],
+        end_color: [1.0, 0.3, 0.0, 0.0],
+        gravity: 9.8,
+        drag: 1.0
+    };
+
+    pub const FIREBALL: Emitter = Emitter {
+        count: 40,
+        speed: (0.5, 2.5),
+        spread: 2.0,
+        lifetime: (0.3, 0.7),
+        size: (0.25, 0.05),
+        start_color: [1.0, 0.7, 0.2, 1.0],
+        end_color: [0.8, 0.1, 0.0, 0.0],
+        gravity: -1.0,
+        drag: 3.0
+    };
+
+    pub const SMOKE: Emitter = Emitter {
+        count: 25,
+        speed: (0.2, 0.8),
+        spread: 0.8,
+        lifetime: (1.5, 3.0),
+        size: (0.15, 0.5),
+        start_color: [0.2, 0.2, 0.2, 0.7],
+        end_color: [0.4, 0.4, 0.4, 0.0],
+        gravity: -0.6,
+        drag: 1.5
+    };
+}
+
+pub struct Particle {
+    pub position: glm::TVec3<f32>,
+    pub velocity: glm::TVec3<f32>,
+    pub age: f32,
+    pub lifetime: f32,
+    emitter: &'static Emitter
+}
+
+impl Particle {
+    //Fraction of the particle's life that has passed, in [0, 1]
+    pub fn life_fraction(&self) -> f32 {
+        f32::min(1.0, self.age / self.lifetime)
+    }
+
+    pub fn size(&self) -> f32 {
+        let t = self.life_fraction();
+        self.emitter.size.0 * (1.0 - t) + self.emitter.size.1 * t
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        let t = self.life_fraction();
+        let mut color = [0.0; 4];
+        for i in 0..color.len() {
+            color[i] = self.emitter.start_color[i] * (1.0 - t) + self.emitter.end_color[i] * t;
+        }
+        color
+    }
+}
+
+pub struct ParticleSystem {
+    particles: Vec<Particle>,
+    max_particles: usize,
+    rng: Rng
+}
+
+impl ParticleSystem {
+    pub const MAX_PARTICLES: usize = 2000;
+    pub const FLOATS_PER_TRANSFORM: usize = 16;
+    pub const FLOATS_PER_COLOR: usize = 4;
+    const BOUNCINESS: f32 = 0.3;
+
+    pub fn new(max_particles: usize) -> Self {
+        ParticleSystem {
+            particles: Vec::with_capacity(max_particles),
+            max_particles,
+            rng: Rng::new(0x9E37_79B9)
+        }
+    }
+
+    pub fn clear(&mut self) { self.particles.clear(); }
+
+    //Spawns a burst of particles at origin heading roughly in direction
+    //A zero direction sprays particles in every direction
+    //Particles that don't fit under max_particles are dropped
+    pub fn emit(&mut self, emitter: &'static Emitter, origin: &glm::TVec3<f32>, direction: &glm::TVec3<f32>) {
+        for _ in 0..emitter.count {
+            if self.particles.len() >= self.max_particles { break; }
+
+            let jitter = self.rng.random_unit_vector();
+            let heading = if glm::length(direction) == 0.0 {
+                jitter
+            } else {
+                let h = glm::normalize(direction) + jitter * emitter.spread;
+                if glm::length(&h) == 0.0 { jitter } else { glm::normalize(&h) }
+            };
+            let speed = self.rng.random_range(emitter.speed);
+            let lifetime = self.rng.random_range(emitter.lifetime);
+
+            self.particles.push(Particle {
+                position: *origin,
+                velocity: heading * speed,
+                age: 0.0,
+                lifetime,
+                emitter
+            });
+        }
+    }
+
+    //Advances every particle by delta_time and removes the ones that have died
+    pub fn update(&mut self, delta_time: f32) {
+        for particle in self.particles.iter_mut() {
+            particle.age += delta_time;
+            particle.velocity.y -= particle.emitter.gravity * delta_time;
+            particle.velocity *= f32::max(0.0, 1.0 - particle.emitter.drag * delta_time);
+            particle.position += particle.velocity * delta_time;
+
+            //Bounce off of the floor
+            if particle.position.y < 0.0 {
+                particle.position.y = 0.0;
+                particle.velocity.y = -particle.velocity.y * Self::BOUNCINESS;
+            }
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    //Writes each particle's transform and color into the given buffers for instanced rendering
+    pub fn fill_instance_buffers(&self, transforms: &mut Vec<f32>, colors: &mut Vec<f32>) {
+        transforms.clear();
+        colors.clear();
+        for particle in self.particles.iter() {
+            let s = particle.size();
+            let p = particle.position;
+            transforms.extend_from_slice(&[
+                s, 0.0, 0.0, 0.0,
+                0.0, s, 0.0, 0.0,
+                0.0, 0.0, s, 0.0,
+                p.x, p.y, p.z, 1.0
+            ]);
+            colors.extend_from_slice(&particle.color());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::*;
+
+
+    //Particles that sit still for exactly one second
+    const STILL: Emitter = Emitter {
+        count: 10,
+        speed: (0.0, 0.0),
+        spread: 0.0,
+        lifetime: (1.0, 1.0),
+        size: (0.5, 0.1),
+        start_color: [1.0, 1.0, 1.0, 1.0],
+        end_color: [0.0, 0.0, 0.0, 0.0],
+        gravity: 0.0,
+        drag: 0.0
+    };
+
+    const HEAVY: Emitter = Emitter { gravity: 10.0, ..STILL };
+    const DRAGGY: Emitter = Emitter { drag: 2.0, ..STILL };
+
+    fn particle(emitter: &'static Emitter, position: glm::TVec3<f32>, velocity: glm::TVec3<f32>, lifetime: f32) -> Particle {
+        Particle {
+            position,
+            velocity,
+            age: 0.0,
+            lifetime,
+            emitter
+        }
+    }
+
+    fn system_with(particles: Vec<Particle>) -> ParticleSystem {
+        let mut system = ParticleSystem::new(ParticleSystem::MAX_PARTICLES);
+        system.particles = particles;
+        system
+    }
+
+    #[test]
+    fn emit_spawns_the_emitters_count() {
+        let mut system = ParticleSystem::new(100);
+        system.emit(&STILL, &glm::vec3(1.0, 2.0, 3.0), &glm::vec3(0.0, 1.0, 0.0));
+        assert_eq!(system.particles.len(), STILL.count);
+        assert!(system.particles.iter().all(|p| p.position == glm::vec3(1.0, 2.0, 3.0) && p.age == 0.0));
+    }
+
+    #[test]
+    fn emit_stops_at_max_particles() {
+        let mut system = ParticleSystem::new(15);
+        system.emit(&STILL, &glm::zero(), &glm::zero());
+        system.emit(&STILL, &glm::zero(), &glm::zero());
+        assert_eq!(system.particles.len(), 15);
+
+        //A full system drops the whole burst
+        system.emit(&STILL, &glm::zero(), &glm::zero());
+        assert_eq!(system.particles.len(), 15);
+    }
+
+    #[test]
+    fn emitted_speeds_stay_in_range() {
+        let mut system = ParticleSystem::new(100);
+        system.emit(&Emitter::SPARKS, &glm::zero(), &glm::zero());
+        for p in system.particles.iter() {
+            let speed = glm::length(&p.velocity);
+            assert!(speed >= Emitter::SPARKS.speed.0 - EPSILON && speed <= Emitter::SPARKS.speed.1 + EPSILON);
+            assert!(p.lifetime >= Emitter::SPARKS.lifetime.0 && p.lifetime <= Emitter::SPARKS.lifetime.1);
+        }
+    }
+
+    #[test]
+    fn update_ages_particles() {
+        let mut system = ParticleSystem::new(100);
+        system.emit(&STILL, &glm::zero(), &glm::zero());
+        system.update(0.25);
+        system.update(0.25);
+        assert_eq!(system.particles.len(), STILL.count);
+        assert!(system.particles.iter().all(|p| close(p.age, 0.5)));
+    }
+
+    #[test]
+    fn update_removes_expired_particles() {
+        let mut system = system_with(vec![
+            particle(&STILL, glm::zero(), glm::zero(), 0.5),
+            particle(&STILL, glm::zero(), glm::zero(), 2.0),
+            particle(&STILL, glm::zero(), glm::zero(), 1.0)
+        ]);
+
+        system.update(0.75);
+        assert_eq!(system.particles.len(), 2);
+
+        //A particle is gone as soon as its age reaches its lifetime
+        system.update(0.25);
+        assert_eq!(system.particles.len(), 1);
+        assert_eq!(system.particles[0].lifetime, 2.0);
+    }
+
+    #[test]
+    fn gravity_pulls_particles_down() {
+        let mut system = system_with(vec![particle(&HEAVY, glm::vec3(0.0, 10.0, 0.0), glm::zero(), 5.0)]);
+        system.update(0.1);
+        let p = &system.particles[0];
+        assert_close(p.velocity.y, -1.0);
+        assert_close(p.position.y, 9.9);
+    }
+
+    #[test]
+    fn drag_slows_particles() {
+        let mut system = system_with(vec![particle(&DRAGGY, glm::vec3(0.0, 1.0, 0.0), glm::vec3(4.0, 0.0, 0.0), 5.0)]);
+        system.update(0.1);
+        let p = &system.particles[0];
+        assert_close(p.velocity.x, 3.2);
+        assert_close(p.position.x, 0.32);
+    }
+
+    #[test]
+    fn drag_never_reverses_velocity() {
+        let mut system = system_with(vec![particle(&DRAGGY, glm::vec3(0.0, 1.0, 0.0), glm::vec3(4.0, 0.0, 0.0), 5.0)]);
+        system.update(1.0);
+        assert_eq!(system.particles[0].velocity.x, 0.0);
+    }
+
+    #[test]
+    fn particles_bounce_off_the_floor() {
+        let mut system = system_with(vec![particle(&STILL, glm::vec3(0.0, 0.05, 0.0), glm::vec3(1.0, -1.0, 0.0), 5.0)]);
+        system.update(0.1);
+        let p = &system.particles[0];
+        assert_eq!(p.position.y, 0.0);
+        assert_close(p.velocity.y, ParticleSystem::BOUNCINESS);
+        assert_close(p.velocity.x, 1.0);
+    }
+
+    #[test]
+    fn instance_buffers_hold_a_transform_and_color_per_particle() {
+        let mut system = system_with(vec![
+            particle(&STILL, glm::vec3(1.0, 2.0, 3.0), glm::zero(), 1.0),
+            particle(&STILL, glm::vec3(4.0, 5.0, 6.0), glm::zero(), 1.0)
+        ]);
+        system.particles[1].age = 0.5;
+
+        let (mut transforms, mut colors) = (vec![9.0; 3], vec![9.0; 3]);
+        system.fill_instance_buffers(&mut transforms, &mut colors);
+        assert_eq!(transforms.len(), 2 * ParticleSystem::FLOATS_PER_TRANSFORM);
+        assert_eq!(colors.len(), 2 * ParticleSystem::FLOATS_PER_COLOR);
+
+        //Uniform scale by the particle's size, then its position in the last column
+        let second = &transforms[ParticleSystem::FLOATS_PER_TRANSFORM..];
+        assert_close(second[0], 0.3);
+        assert_close(second[5], 0.3);
+        assert_close(second[10], 0.3);
+        assert_eq!(&second[12..16], &[4.0, 5.0, 6.0, 1.0]);
+        assert_eq!(&transforms[12..16], &[1.0, 2.0, 3.0, 1.0]);
+
+        //Halfway between the start and end colors
+        assert_eq!(&colors[..4], &STILL.start_color);
+        for c in colors[4..].iter() {
+            assert_close(c, 0.5);
+        }
+    }
+
+    #[test]
+    fn dead_particles_are_not_drawn() {
+        let mut system = ParticleSystem::new(100);
+        system.emit(&STILL, &glm::zero(), &glm::zero());
+        system.update(1.5);
+
+        let (mut transforms, mut colors) = (Vec::new(), Vec::new());
+        system.fill_instance_buffers(&mut transforms, &mut colors);
+        assert!(transforms.is_empty() && colors.is_empty());
+    }
+}
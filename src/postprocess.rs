@@ -0,0 +1,201 @@
+use gl::types::*;
+use std::ptr;
+use crate::render::TextureKeeper;
+use crate::shader::ShaderProgram;
+use crate::structs::ScreenState;
+
+//Every pass the post-processing stack knows how to run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    Bloom,
+    Blur,
+    ToneMapping,            //Brings the HDR scene into [0, 1], so it can't be turned off
+    ColorGrading,
+    Monochrome,
+    Sepia,
+    ChromaticAberration,
+    Vignette,
+    Fxaa
+}
+
+//An ordered list of full-screen passes that the 3D render goes through on its way to the screen
+//Effects run in stack order, ping-ponging between the ScreenState's offscreen targets
+pub struct PostProcessor {
+    stack: Vec<(PostEffect, bool)>,
+    output: usize,                      //Index of the ping-pong target holding the finished image
+    lut_texture: GLuint,
+    tone_map_shader: ShaderProgram,
+    present_shader: ShaderProgram,
+    gaussian_shader: ShaderProgram,
+    bloom_extract_shader: ShaderProgram,
+    bloom_composite_shader: ShaderProgram,
+    color_grading_shader: ShaderProgram,
+    desaturate_shader: ShaderProgram,
+    chromatic_aberration_shader: ShaderProgram,
+    vignette_shader: ShaderProgram,
+    fxaa_shader: ShaderProgram
+}
+
+impl PostProcessor {
+    const BLUR_PASSES: usize = 4;
+    const MONOCHROME_TINT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+    const SEPIA_TINT: [f32; 4] = [1.07, 0.74, 0.43, 1.0];
+
+    //load_program compiles a program from a vertex and fragment shader path, its samplers, and its expected uniforms
+    pub unsafe fn new<F>(load_program: &mut F, texture_keeper: &mut TextureKeeper) -> Self
+        where F: FnMut(&str, &str, &[&'static str], &[&'static str]) -> ShaderProgram {
+        let vertex_path = "shaders/postprocessing.vert";
+        PostProcessor {
+            //Everything but tone mapping is opt-in from the effects menu
+            stack: vec![
+                (PostEffect::Bloom, false),
+                (PostEffect::Blur, false),
+                (PostEffect::ToneMapping, true),
+                (PostEffect::ColorGrading, false),
+                (PostEffect::Monochrome, false),
+                (PostEffect::Sepia, false),
+                (PostEffect::ChromaticAberration, false),
+                (PostEffect::Vignette, false),
+                (PostEffect::Fxaa, false)
+            ],
+            output: 0,
+            lut_texture: texture_keeper.fetch_texture("color_grading", "lut"),
+            tone_map_shader: load_program(vertex_path, "shaders/postprocessing.frag", &["image_texture"], &[]),
+            present_shader: load_program(vertex_path, "shaders/present.frag", &["image_texture"], &[]),
+            gaussian_shader: load_program(vertex_path, "shaders/gaussian_blur.frag", &["image_texture"], &["horizontal"]),
+            bloom_extract_shader: load_program(vertex_path, "shaders/bloom_extract.frag", &["image_texture"], &[]),
+            bloom_composite_shader: load_program(vertex_path, "shaders/bloom_composite.frag", &["image_texture", "bloom_texture"], &[]),
+            color_grading_shader: load_program(vertex_path, "shaders/color_grading.frag", &["image_texture", "lut_texture"], &[]),
+            desaturate_shader: load_program(vertex_path, "shaders/desaturate.frag", &["image_texture"], &["tint"]),
+            chromatic_aberration_shader: load_program(vertex_path, "shaders/chromatic_aberration.frag", &["image_texture"], &[]),
+            vignette_shader: load_program(vertex_path, "shaders/vignette.frag", &["image_texture"], &[]),
+            fxaa_shader: load_program(vertex_path, "shaders/fxaa.frag", &["image_texture"], &[])
+        }
+    }
+
+    pub fn is_enabled(&self, effect: PostEffect) -> bool {
+        self.stack.iter().any(|(e, enabled)| *e == effect && *enabled)
+    }
+
+    pub fn set_enabled(&mut self, effect: PostEffect, enabled: bool) {
+        if effect == PostEffect::ToneMapping { return; }
+
+        for entry in self.stack.iter_mut() {
+            if entry.0 == effect {
+                entry.1 = enabled;
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, effect: PostEffect) {
+        self.set_enabled(effect, !self.is_enabled(effect));
+    }
+
+    //For the hot reloader
+    #[cfg(dev_tools)]
+    pub fn programs_mut(&mut self) -> Vec<&mut ShaderProgram> {
+        vec![
+            &mut self.tone_map_shader,
+            &mut self.present_shader,
+            &mut self.gaussian_shader,
+            &mut self.bloom_extract_shader,
+            &mut self.bloom_composite_shader,
+            &mut self.color_grading_shader,
+            &mut self.desaturate_shader,
+            &mut self.chromatic_aberration_shader,
+            &mut self.vignette_shader,
+            &mut self.fxaa_shader
+        ]
+    }
+
+    //Runs every enabled effect over the scene in ping_pong_fbos[0]
+    //The finished image is left in an offscreen target so that present() can keep showing it while the 3D render is cached
+    pub unsafe fn run(&mut self, screen_state: &ScreenState, vao: GLuint) {
+        gl::BindVertexArray(vao);               //Bind the VAO that just defines a screen-filling triangle
+        gl::ActiveTexture(gl::TEXTURE0);
+
+        let mut source = 0;
+        for i in 0..self.stack.len() {
+            let (effect, enabled) = self.stack[i];
+            if !enabled { continue; }
+
+            match effect {
+                PostEffect::Bloom => {
+                    //Pull out the bright parts at half resolution, then blur them by averaging the mip chain
+                    screen_state.bloom_target.bind();
+                    self.bloom_extract_shader.bind();
+                    gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[source].texture.name());
+                    draw_triangle();
+                    gl::BindTexture(gl::TEXTURE_2D, screen_state.bloom_target.texture.name());
+                    gl::GenerateMipmap(gl::TEXTURE_2D);
+
+                    screen_state.ping_pong_fbos[source ^ 1].bind();
+                    self.bloom_composite_shader.bind();
+                    gl::ActiveTexture(gl::TEXTURE1);
+                    gl::BindTexture(gl::TEXTURE_2D, screen_state.bloom_target.texture.name());
+                    gl::ActiveTexture(gl::TEXTURE0);
+                    gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[source].texture.name());
+                    draw_triangle();
+                    source ^= 1;
+                }
+                PostEffect::Blur => {
+                    self.gaussian_shader.bind();
+                    for _ in 0..Self::BLUR_PASSES {
+                        //Do a horizontal pass followed by a vertical one
+                        for horizontal in [true, false].iter() {
+                            screen_state.ping_pong_fbos[source ^ 1].bind();
+                            gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[source].texture.name());
+                            gl::GenerateMipmap(gl::TEXTURE_2D);                                         //Gen mipmaps so we can source from the downscaled image
+                            self.gaussian_shader.set_int("horizontal", *horizontal as GLint);
+                            draw_triangle();
+                            source ^= 1;
+                        }
+                    }
+                }
+                PostEffect::ColorGrading => {
+                    gl::ActiveTexture(gl::TEXTURE1);
+                    gl::BindTexture(gl::TEXTURE_2D, self.lut_texture);
+                    gl::ActiveTexture(gl::TEXTURE0);
+                    source = single_pass(&self.color_grading_shader, screen_state, source);
+                }
+                PostEffect::Monochrome | PostEffect::Sepia => {
+                    let tint = if effect == PostEffect::Sepia { Self::SEPIA_TINT } else { Self::MONOCHROME_TINT };
+                    self.desaturate_shader.set_vec4("tint", &glm::make_vec4(&tint));
+                    source = single_pass(&self.desaturate_shader, screen_state, source);
+                }
+                PostEffect::ToneMapping => { source = single_pass(&self.tone_map_shader, screen_state, source); }
+                PostEffect::ChromaticAberration => { source = single_pass(&self.chromatic_aberration_shader, screen_state, source); }
+                PostEffect::Vignette => { source = single_pass(&self.vignette_shader, screen_state, source); }
+                PostEffect::Fxaa => { source = single_pass(&self.fxaa_shader, screen_state, source); }
+            }
+        }
+        self.output = source;
+    }
+
+    //The texture holding the finished image
+    pub fn output_texture(&self, screen_state: &ScreenState) -> GLuint {
+        screen_state.ping_pong_fbos[self.output].texture.name()
+    }
+
+    //Copies the finished image into whatever framebuffer is bound
+    pub unsafe fn present(&self, screen_state: &ScreenState, vao: GLuint) {
+        gl::BindVertexArray(vao);
+        gl::ActiveTexture(gl::TEXTURE0);
+        self.present_shader.bind();
+        gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[self.output].texture.name());
+        draw_triangle();
+    }
+}
+
+//Runs program over the image in ping_pong_fbos[source] and returns the index of the target it wrote to
+unsafe fn single_pass(program: &ShaderProgram, screen_state: &ScreenState, source: usize) -> usize {
+    screen_state.ping_pong_fbos[source ^ 1].bind();
+    program.bind();
+    gl::BindTexture(gl::TEXTURE_2D, screen_state.ping_pong_fbos[source].texture.name());
+    draw_triangle();
+    source ^ 1
+}
+
+unsafe fn draw_triangle() {
+    gl::DrawElements(gl::TRIANGLES, 3, gl::UNSIGNED_SHORT, ptr::null());
+}
@@ -1,49 +1,130 @@
 use gl::types::*;
 use ozy_engine::{glutil, routines};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::mem;
+use std::path::Path;
 use std::ptr;
 use std::os::raw::c_void;
+use crate::assets::{self, AssetError, AssetResolver};
+use crate::gl_object::{self, Buffer, GLObjectKind, Texture, VertexArray};
 use crate::DEFAULT_TEX_PARAMS;
 
-pub const MAP_COUNT: usize = 3;      //[albedo, normal, roughness]
-
-pub struct StaticGeometry {
-    pub vao: GLuint,
+//Every texture map describing a surface
+//Texture units match the samplers in shaders/mapped.frag, with unit 3 left for the shadow map
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
     pub albedo: GLuint,
     pub normal: GLuint,
+    pub roughness: GLuint,
+    pub metallic: GLuint,                   //Plain black when the texture set doesn't have one
+    pub ambient_occlusion: GLuint           //Plain white when the texture set doesn't have one
+}
+
+impl Material {
+    pub const ALBEDO_UNIT: GLenum = 0;
+    pub const NORMAL_UNIT: GLenum = 1;
+    pub const ROUGHNESS_UNIT: GLenum = 2;
+    pub const AMBIENT_OCCLUSION_UNIT: GLenum = 4;
+    pub const METALLIC_UNIT: GLenum = 5;
+
+    //Loads the maps in textures/{name}/
+    //Albedo, normal and roughness are required, while metallic and ambient occlusion are used if they're there
+    //Nothing samples height maps, so texture sets that ship one don't get it loaded
+    pub unsafe fn from_texture_set(name: &str, texture_keeper: &mut TextureKeeper) -> Self {
+        let ambient_occlusion = match texture_keeper.fetch_optional_texture(name, "ao") {
+            Some(t) => { Some(t) }
+            None => { texture_keeper.fetch_optional_texture(name, "ambientocclusion") }
+        };
+        let metallic = match texture_keeper.fetch_optional_texture(name, "metallic") {
+            Some(t) => { Some(t) }
+            None => { texture_keeper.fetch_optional_texture(name, "metal") }
+        };
+
+        Material {
+            albedo: texture_keeper.fetch_texture(name, "albedo"),
+            normal: texture_keeper.fetch_texture(name, "normal"),
+            roughness: texture_keeper.fetch_texture(name, "roughness"),
+            metallic: metallic.unwrap_or_else(|| texture_keeper.fallback_texture("metallic")),
+            ambient_occlusion: ambient_occlusion.unwrap_or_else(|| texture_keeper.fallback_texture("ao"))
+        }
+    }
+
+    pub unsafe fn fallback(texture_keeper: &mut TextureKeeper) -> Self {
+        Material {
+            albedo: texture_keeper.fallback_texture("albedo"),
+            normal: texture_keeper.fallback_texture("normal"),
+            roughness: texture_keeper.fallback_texture("roughness"),
+            metallic: texture_keeper.fallback_texture("metallic"),
+            ambient_occlusion: texture_keeper.fallback_texture("ao")
+        }
+    }
+
+    pub unsafe fn bind(&self) {
+        let maps = [
+            (Self::ALBEDO_UNIT, self.albedo),
+            (Self::NORMAL_UNIT, self.normal),
+            (Self::ROUGHNESS_UNIT, self.roughness),
+            (Self::AMBIENT_OCCLUSION_UNIT, self.ambient_occlusion),
+            (Self::METALLIC_UNIT, self.metallic)
+        ];
+        for (unit, tex) in maps.iter() {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, *tex);
+        }
+    }
+}
+
+pub struct StaticGeometry {
+    pub vao: VertexArray,
+    pub material: Material,
     pub model_matrix: glm::TMat4<f32>,
     pub index_count: GLsizei
 }
 
 //One contiguous piece of geometry
 pub struct SimpleMesh {
-    pub vao: GLuint,
+    pub vao: VertexArray,
     pub index_count: GLint,
     pub origin: glm::TVec4<f32>,
-    pub texture_maps: [GLuint; MAP_COUNT]
+    pub material: Material
 }
 
 impl SimpleMesh {
-    pub fn from_ozy(path: &str, texture_keeper: &mut TextureKeeper) -> Self {
+    pub fn from_ozy(path: &str, texture_keeper: &mut TextureKeeper) -> Result<Self, AssetError> {
         match routines::load_ozymesh(path) {
             Some(meshdata) => unsafe {
-                let vao = glutil::create_vertex_array_object(&meshdata.vertex_array.vertices, &meshdata.vertex_array.indices, &meshdata.vertex_array.attribute_offsets);
+                let vao = VertexArray::from_name(glutil::create_vertex_array_object(&meshdata.vertex_array.vertices, &meshdata.vertex_array.indices, &meshdata.vertex_array.attribute_offsets));
                 let count = meshdata.geo_boundaries[1] as GLint;
                 let origin = meshdata.origins[0];
-                let albedo = texture_keeper.fetch_texture(&meshdata.texture_names[0], "albedo");
-                let normal = texture_keeper.fetch_texture(&meshdata.texture_names[0], "normal");
-                let roughness = texture_keeper.fetch_texture(&meshdata.texture_names[0], "roughness");
+                let material = Material::from_texture_set(&meshdata.texture_names[0], texture_keeper);
     
-                SimpleMesh {
+                Ok(SimpleMesh {
                     vao,
                     origin,
-                    texture_maps: [albedo, normal, roughness],
+                    material,
                     index_count: count as GLint
-                }
+                })
             }
             None => {
-                panic!("Unable to load model.");
+                Err(AssetError::from_path(path))
+            }
+        }
+    }
+
+    //Checkerboard cube to stand in for a model that couldn't be loaded
+    pub fn placeholder(half_size: f32, texture_keeper: &mut TextureKeeper) -> Self {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        assets::append_cube(&mut vertices, &mut indices, &glm::zero(), half_size);
+
+        unsafe {
+            SimpleMesh {
+                vao: VertexArray::from_name(glutil::create_vertex_array_object(&vertices, &indices, &[3, 3, 3, 3, 2])),
+                origin: glm::vec4(0.0, 0.0, 0.0, 1.0),
+                material: Material::fallback(texture_keeper),
+                index_count: indices.len() as GLint
             }
         }
     }
@@ -51,7 +132,8 @@ impl SimpleMesh {
 
 pub struct InstancedMesh {
     vao: GLuint,
-    transform_buffer: GLuint,
+    transform_buffer: Buffer,
+    color_buffer: Option<Buffer>,      //None unless enable_instance_colors() has been called
     index_count: GLint,
     active_instances: usize,
     max_instances: usize
@@ -59,15 +141,15 @@ pub struct InstancedMesh {
 
 impl InstancedMesh {
     const FLOATS_PER_TRANSFORM: usize = 16;
+    const FLOATS_PER_COLOR: usize = 4;
 
     pub fn new(vao: GLuint, index_count: GLint, max_instances: usize, instanced_attribute: GLuint) -> Self {
         //Create GPU buffer for instanced matrices
         let transform_buffer = unsafe {
             gl::BindVertexArray(vao);
 
-            let mut b = 0;
-            gl::GenBuffers(1, &mut b);
-            gl::BindBuffer(gl::ARRAY_BUFFER, b);
+            let b = Buffer::new();
+            gl::BindBuffer(gl::ARRAY_BUFFER, b.name());
             gl::BufferData(gl::ARRAY_BUFFER, (max_instances * Self::FLOATS_PER_TRANSFORM * mem::size_of::<GLfloat>()) as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
 
             //Attach this buffer to the shell_mesh vao
@@ -92,15 +174,40 @@ impl InstancedMesh {
             max_instances,
             index_count,
             active_instances: 0,
-            transform_buffer
+            transform_buffer,
+            color_buffer: None
         }
     }
 
+    //Adds a second per-instance buffer holding an RGBA color for each instance
+    pub fn enable_instance_colors(&mut self, color_attribute: GLuint) {
+        self.color_buffer = unsafe {
+            gl::BindVertexArray(self.vao);
+
+            let b = Buffer::new();
+            gl::BindBuffer(gl::ARRAY_BUFFER, b.name());
+            gl::BufferData(gl::ARRAY_BUFFER, (self.max_instances * Self::FLOATS_PER_COLOR * mem::size_of::<GLfloat>()) as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+
+            gl::VertexAttribPointer(color_attribute,
+                                    4,
+                                    gl::FLOAT,
+                                    gl::FALSE,
+                                    (Self::FLOATS_PER_COLOR * mem::size_of::<GLfloat>()) as GLsizei,
+                                    ptr::null());
+            gl::EnableVertexAttribArray(color_attribute);
+            gl::VertexAttribDivisor(color_attribute, 1);
+
+            Some(b)
+        };
+    }
+
     pub unsafe fn draw(&self) {
         gl::BindVertexArray(self.vao);
 		gl::DrawElementsInstanced(gl::TRIANGLES, self.index_count, gl::UNSIGNED_SHORT, ptr::null(), self.active_instances as GLint);
     }
 
+    pub fn active_instances(&self) -> usize { self.active_instances }
+
     pub fn max_instances(&self) -> usize { self.max_instances }
 
     pub fn update_buffer(&mut self, transforms: &[f32]) {
@@ -110,7 +217,7 @@ impl InstancedMesh {
         //Update GPU buffer storing hit volume transforms
 		if transforms.len() > 0 {
 			unsafe {
-				gl::BindBuffer(gl::ARRAY_BUFFER, self.transform_buffer);
+				gl::BindBuffer(gl::ARRAY_BUFFER, self.transform_buffer.name());
 				gl::BufferSubData(gl::ARRAY_BUFFER,
 								0 as GLsizeiptr,
 								(transforms.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
@@ -119,27 +226,347 @@ impl InstancedMesh {
 			}
 		}
     }
+
+    pub fn update_color_buffer(&mut self, colors: &[f32]) {
+		if let (Some(buffer), true) = (&self.color_buffer, colors.len() > 0) {
+			unsafe {
+				gl::BindBuffer(gl::ARRAY_BUFFER, buffer.name());
+				gl::BufferSubData(gl::ARRAY_BUFFER,
+								0 as GLsizeiptr,
+								(colors.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
+								&colors[0] as *const GLfloat as *const c_void
+								);
+			}
+		}
+    }
+}
+
+//Draws every visible copy of a skeleton with one instanced draw call per mesh
+//Each bone has its own buffer of world transforms holding one matrix per instance, which gets attached to the VAO right before that bone's meshes are drawn
+//Several InstancedSkeletons can share a skeleton's VAO since they each re-point the instanced attributes when drawing
+pub struct InstancedSkeleton {
+    vao: GLuint,
+    bone_buffers: Vec<Buffer>,
+    uv_offset_buffer: Buffer,
+    tint_buffer: Buffer,
+    transform_attribute: GLuint,
+    uv_offset_attribute: GLuint,
+    tint_attribute: GLuint,
+    bone_transforms: Vec<Vec<f32>>,             //CPU-side copy of each bone's buffer, filled by push()
+    uv_offsets: Vec<f32>,
+    tints: Vec<f32>,
+    active_instances: usize,
+    max_instances: usize
+}
+
+impl InstancedSkeleton {
+    const FLOATS_PER_TRANSFORM: usize = 16;
+    const FLOATS_PER_UV_OFFSET: usize = 2;
+    const FLOATS_PER_TINT: usize = 4;
+
+    //transform_attribute is the first of the four vec4 attributes making up the model matrix
+    //uv_offset_attribute is only enabled for the meshes passed to draw() as scrolling
+    //Its x scrolls the v coordinate of a mesh's +x half and its y scrolls the -x half, which are a tank's left and right
+    //tint_attribute is a color each instance's albedo is multiplied by
+    pub fn new(skeleton: &Skeleton, max_instances: usize, transform_attribute: GLuint, uv_offset_attribute: GLuint, tint_attribute: GLuint) -> Self {
+        let bone_count = skeleton.bones.len();
+        let create_buffer = |floats_per_instance: usize| unsafe {
+            let b = Buffer::new();
+            gl::BindBuffer(gl::ARRAY_BUFFER, b.name());
+            gl::BufferData(gl::ARRAY_BUFFER, (max_instances * floats_per_instance * mem::size_of::<GLfloat>()) as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+            b
+        };
+        let bone_buffers = (0..bone_count).map(|_| create_buffer(Self::FLOATS_PER_TRANSFORM)).collect();
+        let uv_offset_buffer = create_buffer(Self::FLOATS_PER_UV_OFFSET);
+        let tint_buffer = create_buffer(Self::FLOATS_PER_TINT);
+
+        //Divisors live in the VAO, so they only need setting once
+        unsafe {
+            gl::BindVertexArray(skeleton.vao.name());
+            for i in 0..4 {
+                gl::VertexAttribDivisor(transform_attribute + i, 1);
+            }
+            gl::VertexAttribDivisor(uv_offset_attribute, 1);
+            gl::VertexAttribDivisor(tint_attribute, 1);
+        }
+
+        InstancedSkeleton {
+            vao: skeleton.vao.name(),
+            bone_buffers,
+            uv_offset_buffer,
+            tint_buffer,
+            transform_attribute,
+            uv_offset_attribute,
+            tint_attribute,
+            bone_transforms: (0..bone_count).map(|_| Vec::with_capacity(max_instances * Self::FLOATS_PER_TRANSFORM)).collect(),
+            uv_offsets: Vec::with_capacity(max_instances * Self::FLOATS_PER_UV_OFFSET),
+            tints: Vec::with_capacity(max_instances * Self::FLOATS_PER_TINT),
+            active_instances: 0,
+            max_instances
+        }
+    }
+
+    pub fn active_instances(&self) -> usize { self.active_instances }
+
+    pub fn clear(&mut self) {
+        for transforms in self.bone_transforms.iter_mut() {
+            transforms.clear();
+        }
+        self.uv_offsets.clear();
+        self.tints.clear();
+        self.active_instances = 0;
+    }
+
+    //Adds an instance posed by world-space bone transforms, returning false if the buffers are full
+    pub fn push(&mut self, bone_transforms: &[glm::TMat4<f32>], uv_offset: &glm::TVec2<f32>, tint: &glm::TVec4<f32>) -> bool {
+        if self.active_instances == self.max_instances { return false; }
+
+        for (transforms, bone_transform) in self.bone_transforms.iter_mut().zip(bone_transforms.iter()) {
+            transforms.extend_from_slice(bone_transform.as_slice());
+        }
+        self.uv_offsets.extend_from_slice(uv_offset.as_slice());
+        self.tints.extend_from_slice(tint.as_slice());
+        self.active_instances += 1;
+        true
+    }
+
+    //Sends everything pushed since the last clear() to the GPU
+    pub fn update_buffers(&self) {
+        if self.active_instances == 0 { return; }
+
+        unsafe {
+            for (buffer, transforms) in self.bone_buffers.iter().zip(self.bone_transforms.iter()) {
+                gl::BindBuffer(gl::ARRAY_BUFFER, buffer.name());
+                gl::BufferSubData(gl::ARRAY_BUFFER, 0, (transforms.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, &transforms[0] as *const GLfloat as *const c_void);
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.uv_offset_buffer.name());
+            gl::BufferSubData(gl::ARRAY_BUFFER, 0, (self.uv_offsets.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, &self.uv_offsets[0] as *const GLfloat as *const c_void);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.tint_buffer.name());
+            gl::BufferSubData(gl::ARRAY_BUFFER, 0, (self.tints.len() * mem::size_of::<GLfloat>()) as GLsizeiptr, &self.tints[0] as *const GLfloat as *const c_void);
+        }
+    }
+
+    //Meshes listed in scrolling_meshes get each instance's uv offset, the rest get none
+    //Materials are only bound when bind_materials is set, so the shadow pass can skip them
+    pub unsafe fn draw(&self, skeleton: &Skeleton, scrolling_meshes: &[usize], bind_materials: bool, stats: &mut RenderStats) {
+        if self.active_instances == 0 { return; }
+
+        gl::BindVertexArray(self.vao);
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.uv_offset_buffer.name());
+        gl::VertexAttribPointer(self.uv_offset_attribute, 2, gl::FLOAT, gl::FALSE, (Self::FLOATS_PER_UV_OFFSET * mem::size_of::<GLfloat>()) as GLsizei, ptr::null());
+        gl::BindBuffer(gl::ARRAY_BUFFER, self.tint_buffer.name());
+        gl::VertexAttribPointer(self.tint_attribute, 4, gl::FLOAT, gl::FALSE, (Self::FLOATS_PER_TINT * mem::size_of::<GLfloat>()) as GLsizei, ptr::null());
+        gl::EnableVertexAttribArray(self.tint_attribute);
+        for j in 0..skeleton.node_list.len() {
+            //Attach this mesh's bone's transforms
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.bone_buffers[skeleton.node_list[j]].name());
+            for i in 0..4 {
+                let attribute_index = self.transform_attribute + i;
+                gl::VertexAttribPointer(attribute_index,
+                                        4,
+                                        gl::FLOAT,
+                                        gl::FALSE,
+                                        (Self::FLOATS_PER_TRANSFORM * mem::size_of::<GLfloat>()) as GLsizei,
+                                        (i * 4 * mem::size_of::<GLfloat>() as GLuint) as *const c_void);
+                gl::EnableVertexAttribArray(attribute_index);
+            }
+
+            //A disabled attribute reads as zero
+            if scrolling_meshes.contains(&j) {
+                gl::EnableVertexAttribArray(self.uv_offset_attribute);
+            } else {
+                gl::DisableVertexAttribArray(self.uv_offset_attribute);
+            }
+
+            if bind_materials {
+                skeleton.materials[j].bind();
+            }
+
+            let index_count = (skeleton.geo_boundaries[j + 1] - skeleton.geo_boundaries[j]) as GLint;
+            let offset = (mem::size_of::<GLushort>() * skeleton.geo_boundaries[j] as usize) as *const c_void;
+            gl::DrawElementsInstanced(gl::TRIANGLES, index_count, gl::UNSIGNED_SHORT, offset, self.active_instances as GLint);
+            stats.record_draw(self.active_instances);
+        }
+        gl::DisableVertexAttribArray(self.uv_offset_attribute);
+        gl::DisableVertexAttribArray(self.tint_attribute);
+    }
+}
+
+//How much work the last fresh 3D render took, for the debug overlay
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    pub instances: usize,               //Copies of meshes drawn, counting a non-instanced draw as one
+    pub culled: usize,                  //Objects skipped by frustum culling, counted once per pass
+    pub dropped: usize                  //Instances that didn't fit in their instance buffers, counted once per pass
+}
+
+impl RenderStats {
+    pub fn record_draw(&mut self, instances: usize) {
+        self.draw_calls += 1;
+        self.instances += instances;
+    }
 }
 
 #[derive(Debug)]
 pub struct Skeleton {
-    pub vao: GLuint,
+    pub vao: VertexArray,
 	pub node_list: Vec<usize>,
+	pub mesh_names: Vec<String>,
 	pub geo_boundaries: Vec<u16>,			//[0, a, b, c, ..., indices.length - 1]
-    pub albedo_maps: Vec<GLuint>,
-    pub normal_maps: Vec<GLuint>,
-    pub roughness_maps: Vec<GLuint>,
+    pub materials: Vec<Material>,           //One per mesh
     pub bones: Vec<Bone>,
-    pub bone_origins: Vec<glm::TVec4<f32>>
+    pub bone_origins: Vec<glm::TVec4<f32>>,
+    pub bone_bounds: Vec<(glm::TVec3<f32>, glm::TVec3<f32>)>      //Model-space (min, max) corners of each bone's geometry
 }
 
 impl Skeleton {
+    //Loads a skeleton from an .ozy file, making sure that it contains a bone for each name in required_bones
+    pub fn from_ozy(path: &str, texture_keeper: &mut TextureKeeper, required_bones: &[&str]) -> Result<Self, AssetError> {
+        let meshdata = match routines::load_ozymesh(path) {
+            Some(m) => { m }
+            None => { return Err(AssetError::from_path(path)); }
+        };
+
+        let mesh_count = meshdata.names.len();
+        let bone_count = meshdata.node_ids.iter().map(|id| *id as usize).max().unwrap_or(0);
+        let mut node_list = Vec::with_capacity(mesh_count);
+        let mut mesh_names = Vec::with_capacity(mesh_count);
+        let mut materials = Vec::with_capacity(mesh_count);
+        let mut bones = vec![Bone { transform: glm::identity(), parent: None, name: String::new() }; bone_count];
+        let mut bone_origins = vec![glm::zero(); bone_count];
+        let mut bone_bounds = vec![(glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY), glm::vec3(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY)); bone_count];
+        let vertex_stride: usize = meshdata.vertex_array.attribute_offsets.iter().map(|n| *n as usize).sum();
+
+        //Load node info
+        for i in 0..meshdata.node_ids.len() {
+            let bone = meshdata.node_ids[i] as usize - 1;
+            let parent_id = meshdata.parent_ids[i] as usize;
+            bones[bone].parent = if parent_id == 0 {
+                None
+            } else {
+                Some(parent_id - 1)
+            };
+
+            //A node's own mesh comes after the meshes attached to it, so the last one seen names the bone and sets its origin
+            bones[bone].name = meshdata.names[i].clone();
+            bone_origins[bone] = meshdata.origins[i];
+
+            //Grow the bone's bounds to contain this mesh
+            for k in meshdata.geo_boundaries[i]..meshdata.geo_boundaries[i + 1] {
+                let vertex = meshdata.vertex_array.indices[k as usize] as usize * vertex_stride;
+                let position = glm::vec3(meshdata.vertex_array.vertices[vertex], meshdata.vertex_array.vertices[vertex + 1], meshdata.vertex_array.vertices[vertex + 2]);
+                bone_bounds[bone].0 = glm::min2(&bone_bounds[bone].0, &position);
+                bone_bounds[bone].1 = glm::max2(&bone_bounds[bone].1, &position);
+            }
+            node_list.push(bone);
+            mesh_names.push(meshdata.names[i].clone());
+
+            //Load texture maps
+            unsafe {
+                materials.push(Material::from_texture_set(&meshdata.texture_names[i], texture_keeper));
+            }
+        }
+
+        //Report every required bone the model is missing
+        let missing_bones: Vec<String> = required_bones.iter().filter(|name| !bones.iter().any(|bone| bone.name == **name)).map(|name| name.to_string()).collect();
+        if missing_bones.len() > 0 {
+            return Err(AssetError::MissingBones(path.to_string(), missing_bones));
+        }
+
+        //Create the vertex array object
+        let vao = unsafe { VertexArray::from_name(glutil::create_vertex_array_object(&meshdata.vertex_array.vertices, &meshdata.vertex_array.indices, &meshdata.vertex_array.attribute_offsets)) };
+        Ok(Skeleton {
+            vao,
+            node_list,
+            mesh_names,
+            geo_boundaries: meshdata.geo_boundaries,
+            materials,
+            bones,
+            bone_origins,
+            bone_bounds
+        })
+    }
+
+    //Stack of checkerboard cubes with one bone per name in required_bones, to stand in for a model that couldn't be loaded
+    //The first bone is the root and every other bone is its child
+    pub fn placeholder(texture_keeper: &mut TextureKeeper, required_bones: &[&str]) -> Self {
+        let half_size = 0.25;
+        let bone_count = usize::max(1, required_bones.len());
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut geo_boundaries = vec![0];
+        let mut bones = Vec::with_capacity(bone_count);
+        let mut bone_bounds = Vec::with_capacity(bone_count);
+        for i in 0..bone_count {
+            let center = glm::vec3(0.0, half_size * (2 * i + 1) as f32, 0.0);
+            assets::append_cube(&mut vertices, &mut indices, &center, half_size);
+            geo_boundaries.push(indices.len() as u16);
+
+            let name = match required_bones.get(i) {
+                Some(name) => { name.to_string() }
+                None => { String::from("placeholder") }
+            };
+            bones.push(Bone {
+                transform: glm::identity(),
+                parent: if i == 0 { None } else { Some(0) },
+                name
+            });
+            bone_bounds.push((center - glm::vec3(half_size, half_size, half_size), center + glm::vec3(half_size, half_size, half_size)));
+        }
+
+        unsafe {
+            let material = Material::fallback(texture_keeper);
+            Skeleton {
+                vao: VertexArray::from_name(glutil::create_vertex_array_object(&vertices, &indices, &[3, 3, 3, 3, 2])),
+                node_list: (0..bone_count).collect(),
+                mesh_names: bones.iter().map(|bone| bone.name.clone()).collect(),
+                geo_boundaries,
+                materials: vec![material; bone_count],
+                bones,
+                bone_origins: vec![glm::vec4(0.0, 0.0, 0.0, 1.0); bone_count],
+                bone_bounds
+            }
+        }
+    }
+
+    pub fn bone_by_name(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+
+    pub fn mesh_by_name(&self, name: &str) -> Option<usize> {
+        self.mesh_names.iter().position(|mesh_name| mesh_name == name)
+    }
+
     pub fn get_bones(&self) -> Vec<Bone> {
         self.bones.clone()
     }
 
-    pub unsafe fn draw_bone(&self, index: usize) {
-        gl::DrawElements(gl::TRIANGLES, (self.geo_boundaries[index + 1] - self.geo_boundaries[index]) as i32, gl::UNSIGNED_SHORT, (mem::size_of::<GLushort>() * self.geo_boundaries[index] as usize) as *const c_void);
+    //The local transform of each bone in the skeleton's rest pose
+    pub fn rest_pose(&self) -> Vec<glm::TMat4<f32>> {
+        self.bones.iter().map(|bone| bone.transform).collect()
+    }
+
+    //Computes the world-space transform of every bone by composing its local transform with those of its ancestors
+    pub fn compute_world_transforms(&self, local_transforms: &[glm::TMat4<f32>], world_transforms: &mut [glm::TMat4<f32>]) {
+        let mut computed = vec![false; self.bones.len()];
+        for i in 0..self.bones.len() {
+            self.compute_bone_world_transform(i, local_transforms, world_transforms, &mut computed);
+        }
+    }
+
+    fn compute_bone_world_transform(&self, index: usize, local_transforms: &[glm::TMat4<f32>], world_transforms: &mut [glm::TMat4<f32>], computed: &mut [bool]) {
+        if computed[index] { return; }
+
+        world_transforms[index] = match self.bones[index].parent {
+            Some(parent) => {
+                //Make sure the parent's world transform is up to date before using it
+                self.compute_bone_world_transform(parent, local_transforms, world_transforms, computed);
+                world_transforms[parent] * local_transforms[index]
+            }
+            None => { local_transforms[index] }
+        };
+        computed[index] = true;
     }
 }
 
@@ -147,32 +574,143 @@ impl Skeleton {
 //SkeletonNodes are stored in a flat array, and the value of parent is the index in the array of said node's parent
 #[derive(Clone, Debug)]
 pub struct Bone {
-    pub transform: glm::TMat4<f32>,
-    pub parent: Option<usize>
+    pub transform: glm::TMat4<f32>,         //Rest pose transform relative to the parent bone
+    pub parent: Option<usize>,
+    pub name: String
 }
 
 pub struct TextureKeeper {
-    map: HashMap<String, u32>
+    resolver: AssetResolver,
+    map: HashMap<String, u32>,
+    loaded: Vec<Texture>,                   //Owns every texture in map that isn't a fallback
+    fallbacks: HashMap<String, Texture>,    //Placeholder texture for each map type, created on first use
+    missing: Vec<AssetError>
 }
 
 impl TextureKeeper {
-    pub fn new() -> Self {
+    pub fn new(resolver: AssetResolver) -> Self {
         TextureKeeper {
-            map: HashMap::new()
+            resolver,
+            map: HashMap::new(),
+            loaded: Vec::new(),
+            fallbacks: HashMap::new(),
+            missing: Vec::new()
         }
     }
 
+    //Returns a placeholder texture if the file doesn't exist or isn't a valid PNG, which is recorded as missing once
     pub unsafe fn fetch_texture(&mut self, name: &str, map_type: &str) -> GLuint {        
-		let texture_path = format!("textures/{}/{}.png", name, map_type);
+		let texture_path = self.resolver.resolve(&format!("textures/{}/{}.png", name, map_type));
 		match self.map.get(&texture_path) {
 			Some(t) => { *t }
 			None => {
-				let name = glutil::load_texture(&texture_path, &DEFAULT_TEX_PARAMS);
+				let name = if !Path::new(&texture_path).exists() {
+					self.missing.push(AssetError::Missing(texture_path.clone()));
+					self.fallback_texture(map_type)
+				} else if !Self::png_is_complete(&texture_path) {
+					self.missing.push(AssetError::Unreadable(texture_path.clone()));
+					self.fallback_texture(map_type)
+				} else {
+					let texture = Texture::from_name(glutil::load_texture(&texture_path, &DEFAULT_TEX_PARAMS));
+					let name = texture.name();
+					self.loaded.push(texture);
+					name
+				};
 				self.map.insert(texture_path, name);
 				name
 			}
         }
     }
+
+    //Like fetch_texture() but for maps a texture set doesn't have to provide, so a missing file isn't reported
+    pub unsafe fn fetch_optional_texture(&mut self, name: &str, map_type: &str) -> Option<GLuint> {
+        let texture_path = self.resolver.resolve(&format!("textures/{}/{}.png", name, map_type));
+        if self.map.contains_key(&texture_path) || Path::new(&texture_path).exists() {
+            Some(self.fetch_texture(name, map_type))
+        } else {
+            None
+        }
+    }
+
+    //Re-uploads a texture that has changed on disk into the GL texture it was originally loaded into, so existing handles see the new image
+    //Returns false if the path isn't a texture this keeper loaded
+    pub unsafe fn reload_texture(&mut self, texture_path: &str) -> bool {
+        let tex = match self.map.get(texture_path) {
+            Some(t) => { *t }
+            None => { return false; }
+        };
+
+        //Fallbacks are shared between every missing texture, so one that appeared after startup can't replace them
+        if self.fallbacks.values().any(|fallback| fallback.name() == tex) {
+            println!("\"{}\" was missing at startup, restart to load it", texture_path);
+            return false;
+        }
+
+        //An editor that's halfway through saving leaves a truncated file behind, the next save triggers another reload
+        if !Self::png_is_complete(texture_path) {
+            println!("\"{}\" isn't a valid PNG, keeping the old texture", texture_path);
+            return false;
+        }
+
+        //Decode into a scratch texture and copy its pixels over
+        let scratch = Texture::from_name(glutil::load_texture(texture_path, &DEFAULT_TEX_PARAMS));
+        let (mut width, mut height, mut internal_format) = (0, 0, 0);
+        gl::BindTexture(gl::TEXTURE_2D, scratch.name());
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_WIDTH, &mut width);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_HEIGHT, &mut height);
+        gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 0, gl::TEXTURE_INTERNAL_FORMAT, &mut internal_format);
+        let mut pixels = vec![0u8; (width * height * 4) as usize];
+        gl::GetTexImage(gl::TEXTURE_2D, 0, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut c_void);
+
+        gl::BindTexture(gl::TEXTURE_2D, tex);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, internal_format, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_ptr() as *const c_void);
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+        true
+    }
+
+    //glutil::load_texture() doesn't report decode failures, so files are checked before they get there without decoding them twice
+    //A valid header catches files that aren't PNGs at all and a trailing IEND chunk catches ones that were cut short
+    fn png_is_complete(path: &str) -> bool {
+        const IEND_CHUNK: [u8; 12] = [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82];
+
+        let mut file = match File::open(path) {
+            Ok(f) => { f }
+            Err(_) => { return false; }
+        };
+        if png::Decoder::new(&file).read_info().is_err() {
+            return false;
+        }
+
+        let mut tail = [0; 12];
+        match file.seek(SeekFrom::End(-(IEND_CHUNK.len() as i64))).and_then(|_| file.read_exact(&mut tail)) {
+            Ok(_) => { tail == IEND_CHUNK }
+            Err(_) => { false }
+        }
+    }
+
+    //Normal maps fall back to a flat normal, metallic to a dielectric and ambient occlusion to none at all so that lighting stays sane
+    //A missing color grading LUT leaves colors untouched
+    //Everything else gets the checkerboard
+    pub unsafe fn fallback_texture(&mut self, map_type: &str) -> GLuint {
+        if let Some(t) = self.fallbacks.get(map_type) {
+            return t.name();
+        }
+
+        let tex = match map_type {
+            "normal" => { assets::solid_texture([128, 128, 255, 255]) }
+            "ao" => { assets::solid_texture([255, 255, 255, 255]) }
+            "metallic" => { assets::solid_texture([0, 0, 0, 255]) }
+            "lut" => { assets::identity_lut_texture() }
+            _ => { assets::checkerboard_texture() }
+        };
+        self.fallbacks.insert(map_type.to_string(), Texture::from_name(tex));
+        tex
+    }
+
+    //Hands over every texture that has been found missing since the last call
+    pub fn take_missing(&mut self) -> Vec<AssetError> {
+        mem::take(&mut self.missing)
+    }
 }
 
 pub struct Framebuffer {
@@ -193,6 +731,7 @@ impl Framebuffer {
 
 impl Drop for Framebuffer {
     fn drop(&mut self) {
+        gl_object::untrack(GLObjectKind::Framebuffer, self.name);
         unsafe {
             gl::DeleteFramebuffers(1, &self.name);
         }
@@ -200,25 +739,26 @@ impl Drop for Framebuffer {
 }
 
 //A framebuffer object with color and depth attachments
+//The color attachment is half-float so that lighting can go past 1.0 until it's tone mapped
 pub struct RenderTarget {
     pub framebuffer: Framebuffer,
-    pub texture: GLuint
+    pub texture: Texture,
+    pub depth_texture: Option<Texture>
 }
 
 impl RenderTarget {
     pub unsafe fn new(size: (GLint, GLint)) -> Self {
         let mut fbo = 0;
-		let mut texs = [0; 2];
 		gl::GenFramebuffers(1, &mut fbo);
-		gl::GenTextures(2, &mut texs[0]);
-		let (color_tex, depth_tex) = (texs[0], texs[1]);
+		gl_object::track(GLObjectKind::Framebuffer, fbo);
+		let (color_tex, depth_tex) = (Texture::new(), Texture::new());
 
 		//Initialize the color buffer
-		gl::BindTexture(gl::TEXTURE_2D, color_tex);
+		gl::BindTexture(gl::TEXTURE_2D, color_tex.name());
 		gl::TexImage2D(
 			gl::TEXTURE_2D,
 			0,
-			gl::SRGB8_ALPHA8 as GLint,
+			gl::RGBA16F as GLint,
 			size.0,
 			size.1,
 			0,
@@ -235,7 +775,7 @@ impl RenderTarget {
         glutil::apply_texture_parameters(&params);
 	    gl::GenerateMipmap(gl::TEXTURE_2D);
 
-		gl::BindTexture(gl::TEXTURE_2D, depth_tex);
+		gl::BindTexture(gl::TEXTURE_2D, depth_tex.name());
 		gl::TexImage2D(
 			gl::TEXTURE_2D,
 			0,
@@ -260,14 +800,14 @@ impl RenderTarget {
 			gl::FRAMEBUFFER,
 			gl::COLOR_ATTACHMENT0,
 			gl::TEXTURE_2D,
-			color_tex,
+			color_tex.name(),
 			0
 		);
 		gl::FramebufferTexture2D(
 			gl::FRAMEBUFFER,
 			gl::DEPTH_ATTACHMENT,
 			gl::TEXTURE_2D,
-			depth_tex,
+			depth_tex.name(),
 			0
 		);
 		gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
@@ -281,7 +821,8 @@ impl RenderTarget {
 
 		RenderTarget {
 			framebuffer: f_buffer,
-			texture: color_tex
+			texture: color_tex,
+			depth_texture: Some(depth_tex)
 		}
     }
 
@@ -290,9 +831,6 @@ impl RenderTarget {
     }
 
     pub unsafe fn resize(&mut self, size: (u32, u32)) {
-        gl::DeleteFramebuffers(1, &self.framebuffer.name);
-        let n = Self::new((size.0 as GLint, size.1 as GLint));
-        self.framebuffer = n.framebuffer;
-        self.texture = n.texture;
+        *self = Self::new((size.0 as GLint, size.1 as GLint));
     }
 }
\ No newline at end of file
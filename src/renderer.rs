@@ -0,0 +1,440 @@
+//Draws a frame as a fixed sequence of passes over a queue of draw items built from the world
+use gl::types::*;
+use ozy_engine::glutil;
+use std::ptr;
+use crate::camera::Camera;
+use crate::frustum::Frustum;
+use crate::gl_object::VertexArray;
+use crate::postprocess::PostProcessor;
+use crate::render::{Framebuffer, InstancedMesh, InstancedSkeleton, Material, RenderStats, Skeleton, StaticGeometry, TextureKeeper};
+use crate::shader::ShaderProgram;
+use crate::shadow::{ShadowFilter, ShadowMap, ShadowQuality};
+use crate::structs::{ScreenState, Tank};
+use crate::ui::UIState;
+use crate::TEXTURE_MAP_IDENTIFIERS;
+
+//Something in the world for the 3D passes to draw
+pub enum DrawItem<'a> {
+    Static(&'a StaticGeometry),
+    Tank(&'a Tank<'a>),                             //Every tank is batched into one instanced draw per mesh
+    Instanced(&'a InstancedMesh, &'a Material),     //Lit and shadow casting, like the shells
+    Decals(&'a InstancedMesh),                      //Blended onto the floor, so they should come right after it
+    GroundRings(&'a InstancedMesh),                 //Like decals, but drawn as rings instead of filled shapes
+    Particles(&'a InstancedMesh),                   //Blended over the scene without casting shadows
+    #[cfg(dev_tools)]
+    CollisionVolumes(&'a InstancedMesh)
+}
+
+//Everything the 3D passes need to know about the world for one frame
+pub struct Scene<'a> {
+    pub camera: &'a Camera,
+    pub sun_direction: glm::TVec4<f32>,
+    pub arena_half_size: (f32, f32),                //Half size of the floor in x and z
+    pub items: Vec<DrawItem<'a>>                    //Drawn in order
+}
+
+//GL state a pass draws with, on top of the viewport, clear and cull face that come from its target
+//Items that need something different, like blended ones, switch to their own state block for the length of their draw
+#[derive(Clone, Copy, PartialEq)]
+struct PassState {
+    depth_test: bool,
+    depth_write: bool,
+    polygon_offset: Option<(f32, f32)>,             //(factor, units) for glPolygonOffset()
+    wireframe: bool
+}
+
+impl PassState {
+    const FULL_SCREEN: PassState = PassState {
+        depth_test: false,
+        depth_write: true,
+        polygon_offset: None,
+        wireframe: false
+    };
+
+    unsafe fn apply(&self) {
+        if self.depth_test {
+            gl::Enable(gl::DEPTH_TEST);
+        } else {
+            gl::Disable(gl::DEPTH_TEST);
+        }
+        gl::DepthMask(if self.depth_write { gl::TRUE } else { gl::FALSE });
+
+        match self.polygon_offset {
+            Some((factor, units)) => {
+                gl::Enable(gl::POLYGON_OFFSET_FILL);
+                gl::PolygonOffset(factor, units);
+            }
+            None => { gl::Disable(gl::POLYGON_OFFSET_FILL); }
+        }
+
+        gl::PolygonMode(gl::FRONT_AND_BACK, if self.wireframe { gl::LINE } else { gl::FILL });
+    }
+}
+
+//One step of the frame: the framebuffer it draws into, the textures it reads from, and the state it draws with
+struct Pass<'a> {
+    target: &'a Framebuffer,
+    inputs: &'a [(GLenum, GLuint)],                 //(texture unit, texture) pairs
+    state: PassState
+}
+
+impl<'a> Pass<'a> {
+    unsafe fn begin(&self) {
+        //glClear() respects the depth mask, so make sure the target's depth buffer actually gets cleared
+        gl::DepthMask(gl::TRUE);
+        self.target.bind();
+        self.state.apply();
+
+        for (unit, texture) in self.inputs.iter() {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, *texture);
+        }
+    }
+}
+
+pub struct Renderer {
+    pub shadow_map: ShadowMap,
+    pub shadow_filter: ShadowFilter,
+    pub post_processor: PostProcessor,
+    pub wireframe: bool,
+    #[cfg(dev_tools)]
+    pub visualize_normals: bool,
+    pub stats: RenderStats,                         //From the last fresh 3D render
+    fresh_render_requested: bool,
+    screen_vao: VertexArray,                        //Screen-filling triangle for full-screen passes
+    tank_tread_meshes: Vec<usize>,
+    tank_instances: InstancedSkeleton,              //The shadow and scene passes cull against different frusta, so each gets its own set
+    tank_shadow_instances: InstancedSkeleton,
+    mapped_shader: ShaderProgram,
+    mapped_instanced_shader: ShaderProgram,
+    shadow_shader: ShaderProgram,
+    shadow_instanced_shader: ShaderProgram,
+    prim_instanced_shader: ShaderProgram,
+    particle_shader: ShaderProgram,
+    decal_shader: ShaderProgram,
+    glyph_shader: ShaderProgram,
+    ui_shader: ShaderProgram
+}
+
+impl Renderer {
+    const MAX_TANK_INSTANCES: usize = 256;
+    const TRANSFORM_ATTRIBUTE: GLuint = 5;
+    const UV_OFFSET_ATTRIBUTE: GLuint = 9;
+    const TINT_ATTRIBUTE: GLuint = 10;
+    const SHADOW_MAP_UNIT: GLenum = 3;
+    const COLLISION_VOLUME_COLOR: [f32; 4] = [0.0, 0.0, 1.0, 0.5];
+    const DECAL_POLYGON_OFFSET: (f32, f32) = (-1.0, -4.0);     //Pulls decals toward the camera so they don't z-fight with the floor
+    const GROUND_RING_WIDTH: f32 = 0.2;                         //Fraction of a ground ring's radius
+
+    //load_program compiles a program from a vertex and fragment shader path, its samplers, and its expected uniforms
+    pub unsafe fn new<F>(load_program: &mut F, texture_keeper: &mut TextureKeeper, tank_skeleton: &Skeleton) -> Self
+        where F: FnMut(&str, &str, &[&'static str], &[&'static str]) -> ShaderProgram {
+        //Screen filling triangle with uvs chosen such that the uv range [0, 1] exactly covers the screen
+        let screen_vao = {
+            let vs = [
+                -1.0, -1.0, 0.0, 0.0,
+                3.0, -1.0, 2.0, 0.0,
+                -1.0, 3.0, 0.0, 2.0
+            ];
+            VertexArray::from_name(glutil::create_vertex_array_object(&vs, &[0, 1, 2], &[2, 2]))
+        };
+
+        Renderer {
+            shadow_map: ShadowMap::new(ShadowQuality::High),
+            shadow_filter: ShadowFilter::Pcf,
+            post_processor: PostProcessor::new(load_program, texture_keeper),
+            wireframe: false,
+            #[cfg(dev_tools)]
+            visualize_normals: false,
+            stats: RenderStats::default(),
+            fresh_render_requested: true,
+            screen_vao,
+            tank_tread_meshes: Tank::tread_meshes(tank_skeleton),
+            tank_instances: InstancedSkeleton::new(tank_skeleton, Self::MAX_TANK_INSTANCES, Self::TRANSFORM_ATTRIBUTE, Self::UV_OFFSET_ATTRIBUTE, Self::TINT_ATTRIBUTE),
+            tank_shadow_instances: InstancedSkeleton::new(tank_skeleton, Self::MAX_TANK_INSTANCES, Self::TRANSFORM_ATTRIBUTE, Self::UV_OFFSET_ATTRIBUTE, Self::TINT_ATTRIBUTE),
+            mapped_shader: load_program("shaders/mapped.vert", "shaders/mapped.frag", &TEXTURE_MAP_IDENTIFIERS, &["mvp", "model_matrix", "shadow_matrix", "shadow_extent", "shadow_filter", "uv_offset", "sun_direction", "view_direction"]),
+            mapped_instanced_shader: load_program("shaders/mapped_instanced.vert", "shaders/mapped.frag", &TEXTURE_MAP_IDENTIFIERS, &["view_projection", "shadow_matrix", "shadow_extent", "shadow_filter", "sun_direction", "view_direction"]),
+            shadow_shader: load_program("shaders/shadow.vert", "shaders/shadow.frag", &[], &["mvp"]),
+            shadow_instanced_shader: load_program("shaders/shadow_instanced.vert", "shaders/shadow.frag", &[], &["view_projection"]),
+            prim_instanced_shader: load_program("shaders/prim_instanced.vert", "shaders/prim.frag", &[], &["view_projection", "color"]),
+            particle_shader: load_program("shaders/particle.vert", "shaders/ui_button.frag", &[], &["view_projection"]),
+            decal_shader: load_program("shaders/decal.vert", "shaders/decal.frag", &[], &["view_projection", "ring_width"]),
+            glyph_shader: load_program("shaders/glyph.vert", "shaders/glyph.frag", &["glyph_texture"], &["clipping_from_screen"]),
+            ui_shader: load_program("shaders/ui_button.vert", "shaders/ui_button.frag", &[], &["clipping_from_screen"])
+        }
+    }
+
+    //Makes the next render() draw the 3D scene even if it's allowed to reuse the cached one
+    //Anything that changes how the scene looks while the game isn't being played has to call this
+    pub fn request_fresh_render(&mut self) {
+        self.fresh_render_requested = true;
+    }
+
+    //For the hot reloader
+    #[cfg(dev_tools)]
+    pub fn programs_mut(&mut self) -> Vec<&mut ShaderProgram> {
+        let mut programs = vec![
+            &mut self.mapped_shader,
+            &mut self.mapped_instanced_shader,
+            &mut self.shadow_shader,
+            &mut self.shadow_instanced_shader,
+            &mut self.glyph_shader,
+            &mut self.ui_shader,
+            &mut self.prim_instanced_shader,
+            &mut self.particle_shader,
+            &mut self.decal_shader
+        ];
+        programs.append(&mut self.post_processor.programs_mut());
+        programs
+    }
+
+    //Draws the whole frame into the default framebuffer
+    //When allow_cached is set, the last 3D render is shown again unless a fresh one has been requested
+    pub unsafe fn render(&mut self, scene: &Scene, screen_state: &ScreenState, ui_state: &UIState, allow_cached: bool) {
+        if !allow_cached || self.fresh_render_requested {
+            self.fresh_render_requested = false;
+            self.render_3D(scene, screen_state);
+        }
+
+        //Show the processed render, then draw the UI over it
+        let screen_pass = Pass {
+            target: &screen_state.default_framebuffer,
+            inputs: &[],
+            state: PassState::FULL_SCREEN
+        };
+        screen_pass.begin();
+        self.post_processor.present(screen_state, self.screen_vao.name());
+
+        //The minimap goes under the menus
+        if let Some(minimap) = &ui_state.minimap {
+            if minimap.quad_count() > 0 {
+                draw_ui_elements(minimap.vao.name(), &mut self.ui_shader, minimap.quad_count(), &screen_state.clipping_from_screen);
+            }
+        }
+        if let Some(vao) = &ui_state.buttons_vao {
+            draw_ui_elements(vao.name(), &mut self.ui_shader, ui_state.button_count(), &screen_state.clipping_from_screen);
+        }
+        if let Some(vao) = &ui_state.glyph_vao {
+            gl::ActiveTexture(gl::TEXTURE0);
+            gl::BindTexture(gl::TEXTURE_2D, ui_state.glyph_texture.name());
+            draw_ui_elements(vao.name(), &mut self.glyph_shader, ui_state.glyph_count, &screen_state.clipping_from_screen);
+        }
+    }
+
+    //Shadow pass, scene pass, then post-processing, leaving the result in the post processor's output
+    unsafe fn render_3D(&mut self, scene: &Scene, screen_state: &ScreenState) {
+        //Fit the shadow map to whatever the camera can see this frame
+        self.shadow_map.fit(&scene.sun_direction, scene.arena_half_size, &scene.camera.world_from_clipping);
+
+        self.stats = RenderStats::default();
+        let tank_skeleton = self.batch_tanks(scene);
+        self.set_frame_uniforms(scene);
+
+        //-----------Shadow map rendering-----------
+        //Depths are pushed away from the light in proportion to each polygon's slope to stop shadow acne
+        let shadow_pass = Pass {
+            target: &self.shadow_map.target.framebuffer,
+            inputs: &[],
+            state: PassState {
+                depth_test: true,
+                depth_write: true,
+                polygon_offset: Some((ShadowMap::SLOPE_BIAS, ShadowMap::CONSTANT_BIAS)),
+                wireframe: false
+            }
+        };
+        shadow_pass.begin();
+
+        let mut tanks_drawn = false;
+        for item in scene.items.iter() {
+            match item {
+                DrawItem::Static(piece) => {
+                    self.shadow_shader.bind();
+                    self.shadow_shader.set_mat4("mvp", &(self.shadow_map.matrix * piece.model_matrix));
+                    draw_static(piece, &mut self.stats);
+                }
+                DrawItem::Tank(_) => {
+                    if let (false, Some(skeleton)) = (tanks_drawn, tank_skeleton) {
+                        self.shadow_instanced_shader.bind();
+                        self.tank_shadow_instances.draw(skeleton, &[], false, &mut self.stats);
+                        tanks_drawn = true;
+                    }
+                }
+                DrawItem::Instanced(mesh, _) => {
+                    self.shadow_instanced_shader.bind();
+                    mesh.draw();
+                    self.stats.record_draw(mesh.active_instances());
+                }
+                _ => {}
+            }
+        }
+
+        //-----------Main scene rendering-----------
+        let scene_pass = Pass {
+            target: &screen_state.ping_pong_fbos[0].framebuffer,
+            inputs: &[(Self::SHADOW_MAP_UNIT, self.shadow_map.target.texture.name())],
+            state: PassState {
+                depth_test: true,
+                depth_write: true,
+                polygon_offset: None,
+                wireframe: self.wireframe
+            }
+        };
+        scene_pass.begin();
+
+        let mut tanks_drawn = false;
+        let mut current_state = scene_pass.state;
+        for item in scene.items.iter() {
+            let state = Self::item_state(item, &scene_pass.state);
+            if state != current_state {
+                state.apply();
+                current_state = state;
+            }
+
+            match item {
+                DrawItem::Static(piece) => {
+                    self.mapped_shader.bind();
+                    self.mapped_shader.set_mat4("mvp", &(scene.camera.clipping_from_world * piece.model_matrix));
+                    self.mapped_shader.set_mat4("model_matrix", &piece.model_matrix);
+                    piece.material.bind();
+                    draw_static(piece, &mut self.stats);
+                }
+                DrawItem::Tank(_) => {
+                    //Scroll the texture coordinates of the treads
+                    if let (false, Some(skeleton)) = (tanks_drawn, tank_skeleton) {
+                        self.mapped_instanced_shader.bind();
+                        self.tank_instances.draw(skeleton, &self.tank_tread_meshes, true, &mut self.stats);
+                        tanks_drawn = true;
+                    }
+                }
+                DrawItem::Instanced(mesh, material) => {
+                    //Instanced meshes have no tint buffer, so give the disabled attribute a neutral value
+                    self.mapped_instanced_shader.bind();
+                    gl::VertexAttrib4f(Self::TINT_ATTRIBUTE, 1.0, 1.0, 1.0, 1.0);
+                    material.bind();
+                    mesh.draw();
+                    self.stats.record_draw(mesh.active_instances());
+                }
+                DrawItem::Decals(mesh) => {
+                    self.decal_shader.set_float("ring_width", 0.0);
+                    self.draw_decals(mesh);
+                }
+                DrawItem::GroundRings(mesh) => {
+                    self.decal_shader.set_float("ring_width", Self::GROUND_RING_WIDTH);
+                    self.draw_decals(mesh);
+                }
+                DrawItem::Particles(mesh) => {
+                    self.particle_shader.bind();
+                    mesh.draw();
+                    self.stats.record_draw(mesh.active_instances());
+                }
+                #[cfg(dev_tools)]
+                DrawItem::CollisionVolumes(mesh) => {
+                    self.prim_instanced_shader.bind();
+                    mesh.draw();
+                    self.stats.record_draw(mesh.active_instances());
+                }
+            }
+        }
+
+        //-----------Apply post-processing effects-----------
+        //The post processor binds its own targets
+        PassState::FULL_SCREEN.apply();
+        self.post_processor.run(screen_state, self.screen_vao.name());
+    }
+
+    //The state block an item draws with during the scene pass
+    //Blended items don't write depth so that they blend over each other, and decals lie flat on the floor so they're also offset in depth
+    fn item_state(item: &DrawItem, pass_state: &PassState) -> PassState {
+        match item {
+            DrawItem::Decals(_) | DrawItem::GroundRings(_) => {
+                PassState {
+                    depth_write: false,
+                    polygon_offset: Some(Self::DECAL_POLYGON_OFFSET),
+                    ..*pass_state
+                }
+            }
+            DrawItem::Particles(_) => {
+                PassState {
+                    depth_write: false,
+                    ..*pass_state
+                }
+            }
+            _ => { *pass_state }
+        }
+    }
+
+    unsafe fn draw_decals(&mut self, mesh: &InstancedMesh) {
+        self.decal_shader.bind();
+        mesh.draw();
+        self.stats.record_draw(mesh.active_instances());
+    }
+
+    //Fills the tank instance buffers with only the tanks each pass can see, returning the skeleton they share
+    fn batch_tanks<'a>(&mut self, scene: &Scene<'a>) -> Option<&'a Skeleton> {
+        let camera_frustum = Frustum::from_matrix(&scene.camera.clipping_from_world);
+        let shadow_frustum = Frustum::from_matrix(&self.shadow_map.matrix);
+        let mut skeleton = None;
+
+        self.tank_instances.clear();
+        self.tank_shadow_instances.clear();
+        for item in scene.items.iter() {
+            if let DrawItem::Tank(tank) = item {
+                skeleton = Some(tank.skeleton);
+
+                let (center, radius) = tank.bounding_sphere();
+                if camera_frustum.intersects_sphere(&center, radius) {
+                    if !self.tank_instances.push(&tank.bone_transforms, &glm::vec2(tank.tread_offsets[0], tank.tread_offsets[1]), &tank.tint) {
+                        self.stats.dropped += 1;
+                    }
+                } else {
+                    self.stats.culled += 1;
+                }
+                if shadow_frustum.intersects_sphere(&center, radius) {
+                    if !self.tank_shadow_instances.push(&tank.bone_transforms, &glm::zero(), &tank.tint) {
+                        self.stats.dropped += 1;
+                    }
+                } else {
+                    self.stats.culled += 1;
+                }
+            }
+        }
+        self.tank_instances.update_buffers();
+        self.tank_shadow_instances.update_buffers();
+        skeleton
+    }
+
+    //Set all uniforms that are constant per-frame
+    //These only reach the GL when they've actually changed
+    unsafe fn set_frame_uniforms(&mut self, scene: &Scene) {
+        let view_direction = scene.camera.look_direction();
+        for program in [&mut self.mapped_shader, &mut self.mapped_instanced_shader].iter_mut() {
+            program.set_mat4("shadow_matrix", &self.shadow_map.matrix);
+            program.set_vec2("shadow_extent", &self.shadow_map.extent);
+            program.set_int("shadow_filter", self.shadow_filter as GLint);
+            program.set_vec4("sun_direction", &scene.sun_direction);
+            program.set_vec4("view_direction", &view_direction);
+            #[cfg(dev_tools)]
+            program.set_int("visualize_normals", self.visualize_normals as GLint);
+        }
+        self.mapped_shader.set_vec2("uv_offset", &glm::zero());
+        self.mapped_instanced_shader.set_mat4("view_projection", &scene.camera.clipping_from_world);
+        self.shadow_instanced_shader.set_mat4("view_projection", &self.shadow_map.matrix);
+        self.particle_shader.set_mat4("view_projection", &scene.camera.clipping_from_world);
+        self.decal_shader.set_mat4("view_projection", &scene.camera.clipping_from_world);
+        self.prim_instanced_shader.set_mat4("view_projection", &scene.camera.clipping_from_world);
+        self.prim_instanced_shader.set_vec4("color", &glm::make_vec4(&Self::COLLISION_VOLUME_COLOR));
+    }
+}
+
+unsafe fn draw_static(piece: &StaticGeometry, stats: &mut RenderStats) {
+    gl::BindVertexArray(piece.vao.name());
+    gl::DrawElements(gl::TRIANGLES, piece.index_count, gl::UNSIGNED_SHORT, ptr::null());
+    stats.record_draw(1);
+}
+
+unsafe fn draw_ui_elements(vao: GLuint, shader: &mut ShaderProgram, count: usize, clipping_from_screen: &glm::TMat4<f32>) {
+    shader.bind();
+    shader.set_mat4("clipping_from_screen", clipping_from_screen);
+    gl::BindVertexArray(vao);
+    gl::DrawElements(gl::TRIANGLES, 6 * count as GLint, gl::UNSIGNED_SHORT, ptr::null());
+}
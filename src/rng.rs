@@ -0,0 +1,40 @@
+//Cheap deterministic randomness for visual effects like particles and decals
+//xorshift32 isn't good enough for anything that matters, but it's fast and the same seed always plays out the same way
+
+pub struct Rng {
+    state: u32
+}
+
+impl Rng {
+    //xorshift gets stuck on zero, so a zero seed is swapped for another constant
+    pub fn new(seed: u32) -> Self {
+        Rng {
+            state: if seed == 0 { 0x2545_F491 } else { seed }
+        }
+    }
+
+    //Uniform in [0, 1]
+    pub fn random(&mut self) -> f32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x as f32 / u32::MAX as f32
+    }
+
+    pub fn random_range(&mut self, range: (f32, f32)) -> f32 {
+        range.0 + (range.1 - range.0) * self.random()
+    }
+
+    pub fn random_unit_vector(&mut self) -> glm::TVec3<f32> {
+        //Rejection sample the unit sphere
+        loop {
+            let v = glm::vec3(self.random() * 2.0 - 1.0, self.random() * 2.0 - 1.0, self.random() * 2.0 - 1.0);
+            let length = glm::length(&v);
+            if length > 0.0001 && length <= 1.0 {
+                return v / length;
+            }
+        }
+    }
+}
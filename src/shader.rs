@@ -0,0 +1,224 @@
+use gl::types::*;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs;
+use std::mem;
+use std::path::Path;
+use std::ptr;
+use crate::assets::AssetError;
+use crate::gl_object::{self, GLObjectKind};
+
+#[derive(Clone, Debug, PartialEq)]
+enum UniformValue {
+    Int(GLint),
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec4([f32; 4]),
+    Mat4([f32; 16])
+}
+
+struct Uniform {
+    location: GLint,
+    value: Option<UniformValue>     //Whatever was last uploaded, None if nothing has been since the program was linked
+}
+
+//A linked GLSL program that remembers where its uniforms live and what was last written to them
+//Setting a uniform to the value it already holds doesn't touch the GL
+pub struct ShaderProgram {
+    name: GLuint,
+    vertex_path: String,
+    fragment_path: String,
+    samplers: Vec<&'static str>,
+    uniforms: HashMap<&'static str, Uniform>,
+    problems: Vec<AssetError>
+}
+
+impl ShaderProgram {
+    //Compiles a program from a pair of shader files
+    //Each sampler in samplers is bound to the texture unit matching its index, and every name in samplers and uniforms is checked to be an active uniform
+    //A program that fails to compile is left as program zero, with the failure waiting in take_problems()
+    pub fn from_files(vertex_path: &str, fragment_path: &str, samplers: &[&'static str], uniforms: &[&'static str]) -> Self {
+        let mut program = ShaderProgram {
+            name: 0,
+            vertex_path: vertex_path.to_string(),
+            fragment_path: fragment_path.to_string(),
+            samplers: samplers.to_vec(),
+            uniforms: uniforms.iter().map(|name| (*name, Uniform { location: -1, value: None })).collect(),
+            problems: Vec::new()
+        };
+
+        unsafe {
+            match program.reload() {
+                Ok(_) => {
+                    let missing: Vec<String> = samplers.iter().chain(uniforms.iter()).filter(|name| uniform_location(program.name, name) == -1).map(|name| name.to_string()).collect();
+                    if missing.len() > 0 {
+                        program.problems.push(AssetError::MissingUniforms(program.description(), missing));
+                    }
+                }
+                Err(e) => { program.problems.push(e); }
+            }
+        }
+        program
+    }
+
+    //Recompiles the program from its files
+    //On failure the old program is kept as-is
+    pub unsafe fn reload(&mut self) -> Result<(), AssetError> {
+        let name = match compile_program(&self.vertex_path, &self.fragment_path) {
+            Ok(n) => { n }
+            Err(log) => { return Err(AssetError::ShaderFailed(self.description(), log)); }
+        };
+
+        if self.name != 0 {
+            gl_object::untrack(GLObjectKind::Program, self.name);
+            gl::DeleteProgram(self.name);
+        }
+        self.name = name;
+        gl_object::track(GLObjectKind::Program, name);
+
+        //Linking starts every uniform over from zero
+        for (uniform_name, uniform) in self.uniforms.iter_mut() {
+            uniform.location = uniform_location(name, uniform_name);
+            uniform.value = None;
+        }
+        for (unit, sampler) in self.samplers.iter().enumerate() {
+            gl::ProgramUniform1i(name, uniform_location(name, sampler), unit as GLint);
+        }
+        Ok(())
+    }
+
+    pub fn uses_file(&self, path: &Path) -> bool {
+        Path::new(&self.vertex_path) == path || Path::new(&self.fragment_path) == path
+    }
+
+    pub fn description(&self) -> String {
+        format!("{} + {}", self.vertex_path, self.fragment_path)
+    }
+
+    //Hands over any problems found while loading the program
+    pub fn take_problems(&mut self) -> Vec<AssetError> {
+        mem::take(&mut self.problems)
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::UseProgram(self.name);
+    }
+
+    pub unsafe fn set_int(&mut self, name: &'static str, value: GLint) {
+        if let Some(location) = self.stale_location(name, UniformValue::Int(value)) {
+            gl::ProgramUniform1i(self.name, location, value);
+        }
+    }
+
+    pub unsafe fn set_float(&mut self, name: &'static str, value: f32) {
+        if let Some(location) = self.stale_location(name, UniformValue::Float(value)) {
+            gl::ProgramUniform1f(self.name, location, value);
+        }
+    }
+
+    pub unsafe fn set_vec2(&mut self, name: &'static str, value: &glm::TVec2<f32>) {
+        if let Some(location) = self.stale_location(name, UniformValue::Vec2([value.x, value.y])) {
+            gl::ProgramUniform2f(self.name, location, value.x, value.y);
+        }
+    }
+
+    pub unsafe fn set_vec4(&mut self, name: &'static str, value: &glm::TVec4<f32>) {
+        if let Some(location) = self.stale_location(name, UniformValue::Vec4([value.x, value.y, value.z, value.w])) {
+            gl::ProgramUniform4f(self.name, location, value.x, value.y, value.z, value.w);
+        }
+    }
+
+    pub unsafe fn set_mat4(&mut self, name: &'static str, value: &glm::TMat4<f32>) {
+        let mut floats = [0.0; 16];
+        floats.copy_from_slice(value.as_slice());
+        if let Some(location) = self.stale_location(name, UniformValue::Mat4(floats)) {
+            gl::ProgramUniformMatrix4fv(self.name, location, 1, gl::FALSE, value.as_ptr());
+        }
+    }
+
+    //Records value as the uniform's current value
+    //Returns the uniform's location if the GL needs to be told about it
+    fn stale_location(&mut self, name: &'static str, value: UniformValue) -> Option<GLint> {
+        let program = self.name;
+        let uniform = self.uniforms.entry(name).or_insert_with(|| Uniform { location: uniform_location(program, name), value: None });
+        if uniform.location == -1 || uniform.value.as_ref() == Some(&value) {
+            return None;
+        }
+
+        uniform.value = Some(value);
+        Some(uniform.location)
+    }
+}
+
+impl Drop for ShaderProgram {
+    fn drop(&mut self) {
+        gl_object::untrack(GLObjectKind::Program, self.name);
+        unsafe {
+            gl::DeleteProgram(self.name);
+        }
+    }
+}
+
+fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let c_name = CString::new(name.as_bytes()).unwrap();
+    unsafe { gl::GetUniformLocation(program, c_name.as_ptr()) }
+}
+
+unsafe fn compile_program(vertex_path: &str, fragment_path: &str) -> Result<GLuint, String> {
+    let vertex_shader = compile_shader(gl::VERTEX_SHADER, vertex_path)?;
+    let fragment_shader = match compile_shader(gl::FRAGMENT_SHADER, fragment_path) {
+        Ok(s) => { s }
+        Err(log) => {
+            gl::DeleteShader(vertex_shader);
+            return Err(log);
+        }
+    };
+
+    let program = gl::CreateProgram();
+    gl::AttachShader(program, vertex_shader);
+    gl::AttachShader(program, fragment_shader);
+    gl::LinkProgram(program);
+    gl::DetachShader(program, vertex_shader);
+    gl::DetachShader(program, fragment_shader);
+    gl::DeleteShader(vertex_shader);
+    gl::DeleteShader(fragment_shader);
+
+    let mut status = 0;
+    gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+    if status == gl::FALSE as GLint {
+        let mut length = 0;
+        gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut length);
+        let mut log = vec![0u8; length as usize];
+        gl::GetProgramInfoLog(program, length, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+        gl::DeleteProgram(program);
+        return Err(format!("Link error:\n{}", String::from_utf8_lossy(&log)));
+    }
+    Ok(program)
+}
+
+unsafe fn compile_shader(kind: GLenum, path: &str) -> Result<GLuint, String> {
+    let source = match fs::read_to_string(path) {
+        Ok(s) => { s }
+        Err(e) => { return Err(format!("Couldn't read \"{}\": {}", path, e)); }
+    };
+    let source = match CString::new(source) {
+        Ok(s) => { s }
+        Err(_) => { return Err(format!("\"{}\" contains a nul byte", path)); }
+    };
+
+    let shader = gl::CreateShader(kind);
+    gl::ShaderSource(shader, 1, &source.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut status = 0;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status == gl::FALSE as GLint {
+        let mut length = 0;
+        gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut length);
+        let mut log = vec![0u8; length as usize];
+        gl::GetShaderInfoLog(shader, length, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar);
+        gl::DeleteShader(shader);
+        return Err(format!("\"{}\":\n{}", path, String::from_utf8_lossy(&log)));
+    }
+    Ok(shader)
+}
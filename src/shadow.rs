@@ -0,0 +1,227 @@
+use gl::types::*;
+use ozy_engine::glutil;
+use std::ptr;
+use crate::gl_object::{self, GLObjectKind, Texture};
+use crate::render::{Framebuffer, RenderTarget};
+
+//Resolution tiers for the shadow map, picked from the settings menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowQuality {
+    Low,
+    Medium,
+    High,
+    Ultra
+}
+
+impl ShadowQuality {
+    pub fn resolution(self) -> GLsizei {
+        match self {
+            ShadowQuality::Low => { 1024 }
+            ShadowQuality::Medium => { 2048 }
+            ShadowQuality::High => { 4096 }
+            ShadowQuality::Ultra => { 8192 }
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            ShadowQuality::Low => { ShadowQuality::Medium }
+            ShadowQuality::Medium => { ShadowQuality::High }
+            ShadowQuality::High => { ShadowQuality::Ultra }
+            ShadowQuality::Ultra => { ShadowQuality::Low }
+        }
+    }
+
+    //Text for the settings menu button that cycles through these
+    pub fn label(self) -> String {
+        format!("Shadow quality: {:?} ({}x{})", self, self.resolution(), self.resolution())
+    }
+}
+
+//How the main pass softens shadow edges
+//The discriminants are the values of shadow_filter in shaders/mapped.frag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShadowFilter {
+    Pcf = 0,            //Fixed 3x3 box
+    Poisson = 1,        //Rotated Poisson disk with a fixed radius
+    Pcss = 2            //Poisson disk whose radius grows with the distance to the blocker
+}
+
+impl ShadowFilter {
+    pub fn next(self) -> Self {
+        match self {
+            ShadowFilter::Pcf => { ShadowFilter::Poisson }
+            ShadowFilter::Poisson => { ShadowFilter::Pcss }
+            ShadowFilter::Pcss => { ShadowFilter::Pcf }
+        }
+    }
+
+    pub fn label(self) -> String {
+        format!("Shadow filtering: {:?}", self)
+    }
+}
+
+//Depth map rendered from the sun's point of view
+//The sun's orthographic projection is fitted to the part of the arena the camera can see
+pub struct ShadowMap {
+    pub target: RenderTarget,
+    pub quality: ShadowQuality,
+    pub matrix: glm::TMat4<f32>,        //Shadow clipping space from world space
+    pub extent: glm::TVec2<f32>         //World-space width of the map and depth of its [0, 1] range
+}
+
+impl ShadowMap {
+    const CASTER_HEIGHT: f32 = 2.0;         //Nothing in the arena sticks up further than this
+    const EXTENT_STEP: f32 = 1.0;           //The map's width is rounded up to a multiple of this so the texel size stays put while the view moves
+    const DEPTH_MARGIN: f32 = 1.0;
+
+    //Slope-scaled bias applied with glPolygonOffset() while rendering the map
+    pub const SLOPE_BIAS: f32 = 2.0;
+    pub const CONSTANT_BIAS: f32 = 4.0;
+
+    pub unsafe fn new(quality: ShadowQuality) -> Self {
+        ShadowMap {
+            target: create_target(quality.resolution()),
+            quality,
+            matrix: glm::identity(),
+            extent: glm::vec2(1.0, 1.0)
+        }
+    }
+
+    pub unsafe fn set_quality(&mut self, quality: ShadowQuality) {
+        if quality == self.quality { return; }
+
+        self.target = create_target(quality.resolution());
+        self.quality = quality;
+    }
+
+    //Fits the sun's projection around whatever part of the arena is on screen
+    //arena_half_size is the half size of the floor in x and z
+    //Call this whenever the sun, the camera, the arena, or the quality changes
+    pub fn fit(&mut self, sun_direction: &glm::TVec4<f32>, arena_half_size: (f32, f32), world_from_clipping: &glm::TMat4<f32>) {
+        let light_from_world = glm::mat4(-1.0, 0.0, 0.0, 0.0,
+                                        0.0, 1.0, 0.0, 0.0,
+                                        0.0, 0.0, 1.0, 0.0,
+                                        0.0, 0.0, 0.0, 1.0) * glm::look_at(&glm::vec4_to_vec3(sun_direction), &glm::zero(), &glm::vec3(0.0, 1.0, 0.0));
+
+        //Find the rectangle of floor visible through each corner of the screen, clamped to the arena
+        let mut visible_min = glm::vec2(arena_half_size.0, arena_half_size.1);
+        let mut visible_max = -visible_min;
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].iter() {
+            let near = world_from_clipping * glm::vec4(*x, *y, -1.0, 1.0);
+            let far = world_from_clipping * glm::vec4(*x, *y, 1.0, 1.0);
+            let (near, far) = (near / near.w, far / far.w);
+            let point = if (near.y - far.y).abs() > 0.0001 {
+                let t = near.y / (near.y - far.y);
+                near + (far - near) * t
+            } else {
+                //Looking along the floor, so the whole arena might be visible
+                glm::vec4(x * arena_half_size.0, 0.0, y * arena_half_size.1, 1.0)
+            };
+            visible_min = glm::min2(&visible_min, &glm::vec2(point.x, point.z));
+            visible_max = glm::max2(&visible_max, &glm::vec2(point.x, point.z));
+        }
+        let arena_max = glm::vec2(arena_half_size.0, arena_half_size.1);
+        let visible_min = glm::clamp_vec(&visible_min, &-arena_max, &arena_max);
+        let visible_max = glm::clamp_vec(&visible_max, &-arena_max, &arena_max);
+
+        //The map only has to be as wide as the visible region, but deep enough to catch every caster in the arena
+        let mut light_min = glm::vec3(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut light_max = -light_min;
+        for corner in 0..8 {
+            let height = if corner & 4 == 0 { 0.0 } else { Self::CASTER_HEIGHT };
+            let visible_corner = glm::vec4(
+                if corner & 1 == 0 { visible_min.x } else { visible_max.x },
+                height,
+                if corner & 2 == 0 { visible_min.y } else { visible_max.y },
+                1.0
+            );
+            let arena_corner = glm::vec4(
+                if corner & 1 == 0 { -arena_half_size.0 } else { arena_half_size.0 },
+                height,
+                if corner & 2 == 0 { -arena_half_size.1 } else { arena_half_size.1 },
+                1.0
+            );
+
+            let p = light_from_world * visible_corner;
+            light_min.x = f32::min(light_min.x, p.x);
+            light_min.y = f32::min(light_min.y, p.y);
+            light_max.x = f32::max(light_max.x, p.x);
+            light_max.y = f32::max(light_max.y, p.y);
+
+            let p = light_from_world * arena_corner;
+            light_min.z = f32::min(light_min.z, p.z);
+            light_max.z = f32::max(light_max.z, p.z);
+        }
+
+        //Snap the projection to whole texels so that edges don't shimmer as the fitted region moves
+        let span = f32::max(light_max.x - light_min.x, light_max.y - light_min.y);
+        let width = f32::max(Self::EXTENT_STEP, (span / Self::EXTENT_STEP).ceil() * Self::EXTENT_STEP);
+        let texel_size = width / self.quality.resolution() as f32;
+        let center = (light_min + light_max) * 0.5;
+        let left = ((center.x - width * 0.5) / texel_size).floor() * texel_size;
+        let bottom = ((center.y - width * 0.5) / texel_size).floor() * texel_size;
+
+        //View space looks down -z
+        let near = -light_max.z - Self::DEPTH_MARGIN;
+        let far = -light_min.z + Self::DEPTH_MARGIN;
+
+        self.matrix = glm::ortho(left, left + width, bottom, bottom + width, near, far) * light_from_world;
+        self.extent = glm::vec2(width, far - near);
+    }
+}
+
+unsafe fn create_target(resolution: GLsizei) -> RenderTarget {
+    let mut shadow_framebuffer = 0;
+    gl::GenFramebuffers(1, &mut shadow_framebuffer);
+    gl_object::track(GLObjectKind::Framebuffer, shadow_framebuffer);
+    let shadow_texture = Texture::new();
+
+    //Initialize the texture
+    gl::BindTexture(gl::TEXTURE_2D, shadow_texture.name());
+    gl::TexImage2D(
+        gl::TEXTURE_2D,
+        0,
+        gl::DEPTH_COMPONENT as GLint,
+        resolution,
+        resolution,
+        0,
+        gl::DEPTH_COMPONENT,
+        gl::FLOAT,
+        ptr::null()
+    );
+
+    //Anything outside of the map is unshadowed
+    let params = [
+        (gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER),
+        (gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER),
+        (gl::TEXTURE_MIN_FILTER, gl::NEAREST),
+        (gl::TEXTURE_MAG_FILTER, gl::NEAREST)
+    ];
+    glutil::apply_texture_parameters(&params);
+    let border = [1.0f32; 4];
+    gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, &border[0]);
+
+    gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_framebuffer);
+    gl::FramebufferTexture2D(
+        gl::FRAMEBUFFER,
+        gl::DEPTH_ATTACHMENT,
+        gl::TEXTURE_2D,
+        shadow_texture.name(),
+        0
+    );
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+    let framebuffer = Framebuffer {
+        name: shadow_framebuffer,
+        size: (resolution, resolution),
+        clear_flags: gl::DEPTH_BUFFER_BIT,
+        cull_face: gl::FRONT
+    };
+
+    RenderTarget {
+        framebuffer,
+        texture: shadow_texture,
+        depth_texture: None
+    }
+}
@@ -1,12 +1,14 @@
 use gl::types::*;
 use std::clone::Clone;
 use std::collections::HashMap;
+use crate::collision::OrientedBox;
 use crate::input::{Command, InputKind};
 use crate::render::{Framebuffer, RenderTarget, Skeleton};
 
 pub struct Tank<'a> {
     pub position: glm::TVec3<f32>,
     pub speed: f32,
+    pub mass: f32,
     pub last_shot_time: f32,
     pub live_shells: usize,
     pub firing: bool,
@@ -15,23 +17,53 @@ pub struct Tank<'a> {
     pub rotation: glm::TMat4<f32>,
     pub turret_forward: glm::TVec4<f32>,
     pub skeleton: &'a Skeleton,
+    pub hull_bone: usize,
+    pub turret_bone: usize,
+    pub pitch: f32,
+    pub last_speed: f32,
+    pub tread_offsets: [f32; 2],               //Left then right
+    pub tread_mark_distance: f32,               //How far the tank has rolled since it last left tread marks
+    pub recoil: f32,                            //1.0 right after firing, easing back to 0.0
     pub brain: Brain,
-    pub bone_transforms: Vec<glm::TMat4<f32>>
+    pub tint: glm::TVec4<f32>,                  //Multiplies the albedo of every mesh, so tanks can be told apart
+    pub local_transforms: Vec<glm::TMat4<f32>>,        //Each bone's transform relative to its parent
+    pub bone_transforms: Vec<glm::TMat4<f32>>          //Each bone's transform in world space
 }
 
 impl<'a> Tank<'a> {
     pub const SPEED: f32 = 4.0;
     pub const ROTATION_SPEED: f32 = 3.141592654;
     pub const SHOT_COOLDOWN: f32 = 0.05;
-    pub const HULL_INDEX: usize = 0;
-    pub const TURRET_INDEX: usize = 1;
+    pub const HULL_BONE: &'static str = "Hull";
+    pub const TURRET_BONE: &'static str = "Turret";
+    pub const REQUIRED_BONES: [&'static str; 2] = [Self::HULL_BONE, Self::TURRET_BONE];
+    pub const TREAD_MESHES: [&'static str; 3] = ["TreadFront", "TreadMiddle", "TreadRear"];
+    pub const TREAD_HALF_WIDTH: f32 = 0.5;
+    pub const TREAD_UV_PER_UNIT: f32 = 1.0;
+    pub const PITCH_PER_SPEED: f32 = 0.02;
+    pub const PITCH_SETTLE_TIME: f32 = 0.15;
+    pub const RECOIL_DISTANCE: f32 = 0.15;
+    pub const RECOIL_DURATION: f32 = 0.2;
+    pub const MUZZLE_LENGTH: f32 = 1.0;                 //Distance from the turret's origin to the end of the barrel
     pub const HIT_SPHERE_RADIUS: f32 = 0.4;
     pub const MAX_LIVE_SHELLS: usize = 5;
+    pub const GROUND_RING_RADIUS: f32 = 1.2;           //Outer radius of the ring drawn under the player
     
-    pub fn new(position: glm::TVec3<f32>, forward: glm::TVec3<f32>, skeleton: &'a Skeleton, brain: Brain) -> Self {        
+    //Indices of the skeleton's meshes that get scrolling tread textures
+    pub fn tread_meshes(skeleton: &Skeleton) -> Vec<usize> {
+        Self::TREAD_MESHES.iter().filter_map(|name| skeleton.mesh_by_name(name)).collect()
+    }
+
+    pub fn new(position: glm::TVec3<f32>, forward: glm::TVec3<f32>, skeleton: &'a Skeleton, brain: Brain) -> Self {
+        //Tank skeletons come from either Skeleton::from_ozy(), which rejects models missing REQUIRED_BONES,
+        //or Skeleton::placeholder(), which names its bones after REQUIRED_BONES, so these lookups can't fail
+        let hull_bone = skeleton.bone_by_name(Self::HULL_BONE).expect("Tank skeleton has no hull bone; was it built with Tank::REQUIRED_BONES?");
+        let turret_bone = skeleton.bone_by_name(Self::TURRET_BONE).expect("Tank skeleton has no turret bone; was it built with Tank::REQUIRED_BONES?");
+
         Tank {
             position,
             speed: 0.0,
+            mass: brain.mass(),
             last_shot_time: 0.0,
             live_shells: 0,
             firing: false,
@@ -40,10 +72,83 @@ impl<'a> Tank<'a> {
             rotation: glm::identity(),
             turret_forward: glm::vec4(1.0, 0.0, 0.0, 0.0),
             skeleton,
+            hull_bone,
+            turret_bone,
+            pitch: 0.0,
+            last_speed: 0.0,
+            tread_offsets: [0.0, 0.0],
+            tread_mark_distance: 0.0,
+            recoil: 0.0,
+            tint: brain.tint(),
             brain,
+            local_transforms: skeleton.rest_pose(),
             bone_transforms: vec![glm::identity(); skeleton.bones.len()]
         }
     }
+
+    //Recomputes the world-space bone transforms from the current local transforms
+    pub fn update_bone_transforms(&mut self) {
+        self.skeleton.compute_world_transforms(&self.local_transforms, &mut self.bone_transforms);
+    }
+
+    //Poses a bone by where it should end up in the world, converting through its parent's current world transform
+    //The parent's entry in bone_transforms has to be up to date, so call update_bone_transforms() after posing the parent
+    pub fn set_bone_world_transform(&mut self, bone: usize, world_transform: &glm::TMat4<f32>) {
+        self.local_transforms[bone] = match self.skeleton.bones[bone].parent {
+            Some(parent) => { glm::affine_inverse(self.bone_transforms[parent]) * world_transform }
+            None => { *world_transform }
+        };
+    }
+
+    //World-space (center, radius) of a sphere around every bone in the tank's current pose, used for frustum culling
+    pub fn bounding_sphere(&self) -> (glm::TVec3<f32>, f32) {
+        let bone_sphere = |bone: usize| {
+            let (bone_min, bone_max) = self.skeleton.bone_bounds[bone];
+            let local_center = (bone_min + bone_max) / 2.0;
+            let center = self.bone_transforms[bone] * glm::vec4(local_center.x, local_center.y, local_center.z, 1.0);
+            (glm::vec4_to_vec3(&center), glm::length(&(bone_max - bone_min)) / 2.0)
+        };
+
+        let (center, mut radius) = bone_sphere(self.hull_bone);
+        for bone in 0..self.skeleton.bone_bounds.len() {
+            //Bones without any geometry have inverted bounds
+            let (bone_min, bone_max) = self.skeleton.bone_bounds[bone];
+            if bone_min.x > bone_max.x { continue; }
+
+            let (bone_center, bone_radius) = bone_sphere(bone);
+            radius = f32::max(radius, glm::distance(&center, &bone_center) + bone_radius);
+        }
+        (center, radius)
+    }
+
+    //The tank's hull bounds in world space, used for tank-vs-tank collision
+    pub fn hull_box(&self) -> OrientedBox {
+        let x_axis = -glm::cross(&self.forward, &glm::vec3(0.0, 1.0, 0.0));
+        let (hull_min, hull_max) = self.skeleton.bone_bounds[self.hull_bone];
+        let local_center = (hull_min + hull_max) / 2.0;
+        let half_extents = (hull_max - hull_min) / 2.0;
+
+        OrientedBox {
+            center: self.position + x_axis * local_center.x + self.forward * local_center.z,
+            axes: [x_axis, self.forward],
+            half_extents: glm::vec2(half_extents.x, half_extents.z)
+        }
+    }
+
+    //Slides the tank back onto the floor if any part of its hull hangs over the edge
+    //arena_half_size is the half size of the floor in x and z
+    pub fn clamp_to_arena(&mut self, arena_half_size: (f32, f32)) {
+        let hull = self.hull_box();
+        let reach = |axis: usize| {
+            hull.half_extents.x * hull.axes[0][axis].abs() + hull.half_extents.y * hull.axes[1][axis].abs()
+        };
+        let limits = [(0, arena_half_size.0 - reach(0)), (2, arena_half_size.1 - reach(2))];
+        for (axis, limit) in limits.iter() {
+            let center = hull.center[*axis];
+            let clamped = f32::max(-limit, f32::min(*limit, center));
+            self.position[*axis] += clamped - center;
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -52,13 +157,27 @@ pub struct Shell {
     pub velocity: glm::TVec4<f32>,
     pub transform: glm::TMat4<f32>,
     pub spawn_time: f32,
-    pub shooter: usize
+    pub shooter: usize,
+    pub ricochets: usize
 }
 
 impl Shell {
     pub const VELOCITY: f32 = 6.0;
     pub const LIFETIME: f32 = 4.0;
     pub const HIT_SPHERE_RADIUS: f32 = 0.05;
+    pub const MAX_RICOCHETS: usize = 1;
+}
+
+//Things that happen during the simulation step that other systems react to
+//Each frame these are pushed to the event buffer, then drained once the simulation step is done
+#[derive(Debug, Clone, Copy)]
+pub enum SimulationEvent {
+    ShellFired(glm::TVec3<f32>, glm::TVec3<f32>),      //Muzzle position and firing direction
+    ShellRicochet(glm::TVec3<f32>),
+    ShellCollision(glm::TVec3<f32>),
+    ShellExpired(glm::TVec3<f32>),
+    TankDestroyed(glm::TVec3<f32>),
+    TreadMarks(glm::TVec3<f32>, glm::TVec3<f32>)       //Tank position and forward
 }
 
 //Determines what to do during the update step for a given entity
@@ -67,6 +186,25 @@ pub enum Brain {
     DumbAI,
 }
 
+impl Brain {
+    //Each kind of tank gets its own team color
+    pub fn tint(&self) -> glm::TVec4<f32> {
+        match self {
+            Brain::PlayerInput => { glm::vec4(0.55, 0.75, 1.0, 1.0) }
+            Brain::DumbAI => { glm::vec4(1.0, 0.6, 0.5, 1.0) }
+        }
+    }
+
+    //How hard each kind of tank is to shove around in tank-vs-tank collisions
+    //The player's tank is heavier so that a crowd of enemies can't bully it into a corner
+    pub fn mass(&self) -> f32 {
+        match self {
+            Brain::PlayerInput => { 2.0 }
+            Brain::DumbAI => { 1.0 }
+        }
+    }
+}
+
 pub struct GameState {
     pub kind: GameStateKind,
     input_maps: HashMap<GameStateKind, HashMap<(InputKind, glfw::Action), Command>>
@@ -96,30 +234,18 @@ pub enum GameStateKind {
     Paused
 }
 
-pub enum ImageEffect {
-    Blur,
-    None
-}
-
 pub struct ScreenState {
     pub window_size: (u32, u32),
     pub aspect_ratio: f32,
     pub ping_pong_fbos: [RenderTarget; 2],
+    pub bloom_target: RenderTarget,             //Half resolution, and its mip chain does the blurring
     pub default_framebuffer: Framebuffer,
-    pub clipping_from_view: glm::TMat4<f32>,
-    pub clipping_from_world: glm::TMat4<f32>,
-    pub world_from_clipping: glm::TMat4<f32>,
     pub clipping_from_screen: glm::TMat4<f32>
 }
 
 impl ScreenState {
-    const ORTHO_SIZE: f32 = 5.0;
-
-    pub fn new(window_size: (u32, u32), view_from_world: &glm::TMat4<f32>) -> Self {
+    pub fn new(window_size: (u32, u32)) -> Self {
         let aspect_ratio = window_size.0 as f32 / window_size.1 as f32;
-        let clipping_from_view = glm::ortho(-Self::ORTHO_SIZE*aspect_ratio, Self::ORTHO_SIZE*aspect_ratio, -Self::ORTHO_SIZE, Self::ORTHO_SIZE, -Self::ORTHO_SIZE, Self::ORTHO_SIZE * 2.0);
-        let clipping_from_world = clipping_from_view * view_from_world;
-        let world_from_clipping = glm::affine_inverse(clipping_from_world);
         let clipping_from_screen = glm::mat4(
             2.0 / window_size.0 as f32, 0.0, 0.0, -1.0,
             0.0, -(2.0 / window_size.1 as f32), 0.0, 1.0,
@@ -132,6 +258,7 @@ impl ScreenState {
             let size = (window_size.0 as GLint, window_size.1 as GLint);
             [RenderTarget::new(size), RenderTarget::new(size)]            
         };
+        let bloom_target = unsafe { RenderTarget::new((window_size.0 as GLint / 2, window_size.1 as GLint / 2)) };
 
         //Initialize default framebuffer
         let default_framebuffer = Framebuffer {
@@ -145,10 +272,8 @@ impl ScreenState {
             window_size,
             aspect_ratio,
             ping_pong_fbos,
+            bloom_target,
             default_framebuffer,
-            clipping_from_view,
-            clipping_from_world,
-            world_from_clipping,
             clipping_from_screen
         }
     }
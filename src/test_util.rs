@@ -0,0 +1,13 @@
+//Helpers shared by the unit tests of every module
+
+//Tolerance for comparing floats that went through a bit of arithmetic
+pub const EPSILON: f32 = 0.0001;
+
+pub fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < EPSILON
+}
+
+#[track_caller]
+pub fn assert_close(actual: f32, expected: f32) {
+    assert!(close(actual, expected), "expected {} but got {}", expected, actual);
+}
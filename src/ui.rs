@@ -1,8 +1,11 @@
 use crate::input::{Command};
+use crate::gl_object::{Buffer, Texture, VertexArray};
+use crate::minimap::{Minimap, MinimapBlip};
 use ozy_engine::structs::{OptionVec};
 use ozy_engine::glutil;
-use glyph_brush::{BrushAction, BrushError, GlyphBrush, GlyphCruncher, GlyphVertex, ab_glyph::PxScale, Section, Rectangle, Text};
+use glyph_brush::{BrushAction, BrushError, GlyphBrush, GlyphCruncher, GlyphVertex, ab_glyph::PxScale, OwnedSection, Section, Rectangle, Text};
 use gl::types::*;
+use std::borrow::Cow;
 use std::os::raw::c_void;
 use std::{mem, ptr};
 
@@ -59,7 +62,7 @@ pub struct UIInternals<'a> {
 	pub glyph_brush: &'a mut GlyphBrush<GlyphBrushVertexType>,
 	window_size: (u32, u32),
     buttons: OptionVec<UIButton>,
-    sections: OptionVec<Section<'a>>
+    sections: OptionVec<OwnedSection>       //Owned so that labels built at runtime don't have to outlive the UI
 }
 
 impl<'a> UIInternals<'a> {
@@ -80,7 +83,7 @@ impl<'a> UIInternals<'a> {
 
     pub fn add_section(&mut self, section: Section<'a>) -> usize {
         self.vao_flag = true;
-        self.sections.insert(section)
+        self.sections.insert(section.to_owned())
     }
 
     pub fn delete_button(&mut self, index: usize) {
@@ -98,12 +101,13 @@ impl<'a> UIInternals<'a> {
 }
 
 pub struct UIState<'a> {
-    pub button_color_buffer: GLuint,
-    pub buttons_vao: Option<GLuint>,
+    pub button_color_buffer: Option<Buffer>,
+    pub buttons_vao: Option<VertexArray>,
     pub internals: UIInternals<'a>,
-    pub glyph_texture: GLuint,
-    pub glyph_vao: Option<GLuint>,
+    pub glyph_texture: Texture,
+    pub glyph_vao: Option<VertexArray>,
 	pub glyph_count: usize,
+	pub minimap: Option<Minimap>,
 	menu_chains: Vec<Vec<usize>>, //Array of array of menu ids used for nested menu traversal
 	menus: Vec<Menu<'a>>,
 	text_elements: Vec<UIText<'a>>
@@ -117,10 +121,9 @@ impl<'a> UIState<'a> {
         //Create the glyph texture
         let glyph_texture = unsafe {
             let (width, height) = glyph_brush.texture_dimensions();
-            let mut tex = 0;
+            let tex = Texture::new();
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
-            gl::GenTextures(1, &mut tex);
-            gl::BindTexture(gl::TEXTURE_2D, tex);
+            gl::BindTexture(gl::TEXTURE_2D, tex.name());
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
             gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
@@ -130,12 +133,13 @@ impl<'a> UIState<'a> {
         };
 
         UIState {
-            button_color_buffer: 0,
+            button_color_buffer: None,
             buttons_vao: None,
             internals: UIInternals::new(glyph_brush, window_size),
             glyph_texture,
             glyph_vao: None,
 			glyph_count: 0,
+			minimap: None,
 			menu_chains: Vec::new(),
 			menus: Vec::new(),
 			text_elements: Vec::new()
@@ -226,6 +230,13 @@ impl<'a> UIState<'a> {
 		}
 	}
 	
+	//Rebuilds the minimap, if there is one, from the current simulation state
+	pub fn update_minimap(&mut self, arena_half_size: (f32, f32), blips: &[MinimapBlip]) {
+		if let Some(minimap) = &mut self.minimap {
+			minimap.update(self.internals.window_size, arena_half_size, blips);
+		}
+	}
+
 	pub fn rollback_chain(&mut self, chain: usize) {
 		if let Some(index) = self.menu_chains[chain].pop() {
 			self.hide_menu(index);
@@ -237,6 +248,10 @@ impl<'a> UIState<'a> {
 		}
 	}
 
+	pub fn set_menu_label(&mut self, menu: usize, button: usize, label: String) {
+		self.menus[menu].set_label(button, label, &mut self.internals);
+	}
+
 	pub fn set_menus(&mut self, menus: Vec<Menu<'a>>) {
 		self.menus = menus;
 	}
@@ -245,6 +260,18 @@ impl<'a> UIState<'a> {
 		self.text_elements = texts;
 	}
 
+	pub fn set_text(&mut self, index: usize, text: String) {
+		self.text_elements[index].set_text(text, &mut self.internals);
+	}
+
+	pub fn show_text_element(&mut self, index: usize) {
+		self.text_elements[index].show(&mut self.internals);
+	}
+
+	pub fn hide_text_element(&mut self, index: usize) {
+		self.text_elements[index].hide(&mut self.internals);
+	}
+
 	fn show_menu(&mut self, index: usize) { self.menus[index].show(&mut self.internals); }
 
 	//Call this function each frame right before rendering
@@ -273,9 +300,11 @@ impl<'a> UIState<'a> {
 
     //Gets input from the UI system and generates Commands for the command buffer I.E. user clicking on buttons
     //Also updates the instanced color buffer used for rendering the buttons
+    //Returns true if a button was clicked this frame
     //Meant to be called once per frame
-    pub fn update_buttons(&mut self, screen_space_mouse: glm::TVec2<f32>, mouse_lbutton_pressed: bool, mouse_lbutton_pressed_last_frame: bool, command_buffer: &mut Vec<Command>) {        
+    pub fn update_buttons(&mut self, screen_space_mouse: glm::TVec2<f32>, mouse_lbutton_pressed: bool, mouse_lbutton_pressed_last_frame: bool, command_buffer: &mut Vec<Command>) -> bool {
 		//Handle input from the UI buttons
+		let mut clicked = false;
 		let mut current_button = 0;
 		for i in 0..self.internals.buttons.len() {
 			if let Some(button) = self.internals.buttons.get_mut_element(i) {
@@ -285,6 +314,7 @@ impl<'a> UIState<'a> {
 				   screen_space_mouse.y < button.bounds.max[1] {
 
 					if mouse_lbutton_pressed_last_frame && !mouse_lbutton_pressed {
+						clicked = true;
 						if let Some(command) = button.command {
 							command_buffer.push(command);
 						}
@@ -297,14 +327,18 @@ impl<'a> UIState<'a> {
 						} else {
 							[0.0, 0.4, 0.0, 0.5]
 						};
-						unsafe { Self::update_ui_button_color(self.button_color_buffer, current_button, color); }
+						if let Some(buffer) = &self.button_color_buffer {
+							unsafe { Self::update_ui_button_color(buffer.name(), current_button, color); }
+						}
 
 						button.state = ButtonState::Highlighted;
 					}
 				} else {
 					if button.state != ButtonState::None {
 						let color = [0.0, 0.0, 0.0, 0.5];
-						unsafe { Self::update_ui_button_color(self.button_color_buffer, current_button, color); }
+						if let Some(buffer) = &self.button_color_buffer {
+							unsafe { Self::update_ui_button_color(buffer.name(), current_button, color); }
+						}
 
 						button.state = ButtonState::None;
 					}
@@ -312,10 +346,11 @@ impl<'a> UIState<'a> {
 				current_button += 1;
 			}
 		}
+		clicked
 	}
 
     fn glyph_processing(&mut self) {
-        let glyph_tex = self.glyph_texture;
+        let glyph_tex = self.glyph_texture.name();
 
         //glyph_brush processing
 		let mut glyph_result = self.internals.glyph_brush.process_queued(|rect, tex_data| unsafe { 
@@ -326,7 +361,7 @@ impl<'a> UIState<'a> {
 		while let Err(BrushError::TextureTooSmall { suggested }) = glyph_result {
 			let (width, height) = suggested;
 			unsafe {
-				gl::BindTexture(gl::TEXTURE_2D, self.glyph_texture);
+				gl::BindTexture(gl::TEXTURE_2D, glyph_tex);
 				gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RED as GLint, width as GLint, height as GLint, 0, gl::RED, gl::UNSIGNED_BYTE, ptr::null());
 			}
 			self.internals.glyph_brush.resize_texture(width, height);
@@ -352,20 +387,10 @@ impl<'a> UIState<'a> {
 					self.glyph_count = verts.len();
 
 					let attribute_strides = [2, 2, 4];
-					match self.glyph_vao {
-						Some(mut vao) => unsafe {
-							gl::DeleteVertexArrays(1, &mut vao);
-							self.glyph_vao = Some(glutil::create_vertex_array_object(&vertex_buffer, &index_buffer, &attribute_strides));
-						}
-						None => unsafe {
-							self.glyph_vao = Some(glutil::create_vertex_array_object(&vertex_buffer, &index_buffer, &attribute_strides));
-						}
-					}
+					//Replacing the old vao deletes it
+					self.glyph_vao = Some(unsafe { VertexArray::from_name(glutil::create_vertex_array_object(&vertex_buffer, &index_buffer, &attribute_strides)) });
 				} else {
-					if let Some(mut vao) = self.glyph_vao {
-						unsafe { gl::DeleteVertexArrays(1, &mut vao); }
-						self.glyph_vao = None;
-					}
+					self.glyph_vao = None;
 				}
 			}
 			BrushAction::ReDraw => {}
@@ -375,7 +400,7 @@ impl<'a> UIState<'a> {
     fn queue_sections(&mut self) {
         for sec in self.internals.sections.iter() {
 			if let Some(s) = sec {
-				self.internals.glyph_brush.queue(s);
+				self.internals.glyph_brush.queue(s.to_borrowed());
 			}
 		}
     }
@@ -407,21 +432,11 @@ impl<'a> UIState<'a> {
 					}
 				}
 
-				match self.buttons_vao {
-					Some(mut vao) => {
-						gl::DeleteVertexArrays(1, &mut vao);
-						self.buttons_vao = Some(glutil::create_vertex_array_object(&vertices, &indices, &[2]));
-						gl::BindVertexArray(vao);
-					}
-					None => {
-						let vao = glutil::create_vertex_array_object(&vertices, &indices, &[2]);
-						self.buttons_vao = Some(vao);
-						gl::BindVertexArray(vao);
-					}
-				}
+				//from_name() leaves the new vao bound for the color buffer to attach to
+				self.buttons_vao = Some(VertexArray::from_name(glutil::create_vertex_array_object(&vertices, &indices, &[2])));
 
 				//Create GPU buffer for ui button colors
-				self.button_color_buffer = {
+				self.button_color_buffer = Some({
 					let element_count = self.button_count() * UIState::COLORS_PER_BUTTON * UIState::FLOATS_PER_COLOR;
 
 					let mut data = vec![0.0f32; element_count];
@@ -432,9 +447,8 @@ impl<'a> UIState<'a> {
 						data[i * 4 + 3] = 0.5;
 					}
 
-					let mut b = 0;
-					gl::GenBuffers(1, &mut b);
-					gl::BindBuffer(gl::ARRAY_BUFFER, b);
+					let b = Buffer::new();
+					gl::BindBuffer(gl::ARRAY_BUFFER, b.name());
 					gl::BufferData(gl::ARRAY_BUFFER, (element_count * mem::size_of::<GLfloat>()) as GLsizeiptr, &data[0] as *const f32 as *const c_void, gl::DYNAMIC_DRAW);
 
 					//Attach buffer to vao
@@ -447,13 +461,11 @@ impl<'a> UIState<'a> {
 					gl::EnableVertexAttribArray(1);
 
 					b
-				};
+				});
 			}
 		} else if self.button_count() == 0 {
-			if let Some(mut vao) = self.buttons_vao {
-				unsafe { gl::DeleteVertexArrays(1, &mut vao); }
-				self.buttons_vao = None;
-			}
+			self.buttons_vao = None;
+			self.button_color_buffer = None;
 		}
     }
 
@@ -476,7 +488,7 @@ impl<'a> UIState<'a> {
 }
 
 pub struct UIText<'a> {
-	text: &'a str,
+	text: Cow<'a, str>,
 	font_size: f32,
 	color: [f32; 4],
 	anchor: UIAnchor,
@@ -487,7 +499,7 @@ pub struct UIText<'a> {
 impl<'a> UIText<'a> {
 	pub fn new(text: &'a str, font_size: f32, anchor: UIAnchor) -> Self {
 		UIText {
-			text,
+			text: Cow::Borrowed(text),
 			font_size,
 			color: [1.0, 1.0, 1.0, 1.0],
 			anchor,
@@ -503,7 +515,7 @@ impl<'a> UIText<'a> {
 		//Create the section
 		let the_section = {
 			let section = Section::new();
-			let mut text = Text::new(self.text).with_color(self.color);
+			let mut text = Text::new(&self.text).with_color(self.color);
 			text.scale = PxScale::from(self.font_size);
 			let mut section = section.add_text(text);
 		
@@ -527,12 +539,24 @@ impl<'a> UIText<'a> {
 
 					(x_pos, offset)
 				}
+				UIAnchor::RightAligned((x, y)) => {
+					(internals.window_size.0 as f32 - x - bounding_box.width(), y)
+				}
 			};
 			
 			section
 		};
 
-		self.index = Some(internals.sections.insert(the_section));
+		self.index = Some(internals.sections.insert(the_section.to_owned()));
+	}
+
+	//Replaces the text, rebuilding the element if it's on screen
+	pub fn set_text(&mut self, text: String, internals: &mut UIInternals<'a>) {
+		self.text = Cow::Owned(text);
+		if self.active {
+			self.hide(internals);
+			self.show(internals);
+		}
 	}
 
 	pub fn hide(&mut self, internals: &mut UIInternals) {
@@ -576,7 +600,7 @@ impl UIButton {
 }
 
 pub struct Menu<'a> {
-	button_labels: Vec<&'a str>,
+	button_labels: Vec<Cow<'a, str>>,
 	button_commands: Vec<Option<Command>>,
 	label_colors: Vec<[f32; 4]>,
     anchor: UIAnchor,
@@ -592,7 +616,7 @@ impl<'a> Menu<'a> {
 
 		//lol
 		for butt in buttons.iter() {
-			button_labels.push(butt.0);
+			button_labels.push(Cow::Borrowed(butt.0));
 			button_commands.push(butt.1);
 		}
 
@@ -614,7 +638,7 @@ impl<'a> Menu<'a> {
 		let mut button_commands = Vec::with_capacity(size);
 		let mut label_colors = Vec::with_capacity(size);
 		for butt in buttons.iter() {
-			button_labels.push(butt.0);
+			button_labels.push(Cow::Borrowed(butt.0));
 			button_commands.push(butt.1);
 			label_colors.push(butt.2);
 		}
@@ -640,7 +664,7 @@ impl<'a> Menu<'a> {
 		for i in 0..self.button_labels.len() {
 			let mut section = {
 				let section = Section::new();
-				let mut text = Text::new(self.button_labels[i]).with_color(self.label_colors[i]);
+				let mut text = Text::new(&self.button_labels[i]).with_color(self.label_colors[i]);
 				text.scale = PxScale::from(font_size);
 				section.add_text(text)
 			};
@@ -678,6 +702,14 @@ impl<'a> Menu<'a> {
                     glyph_brush::Rectangle {
                         min: [x_pos, y_pos],
                         max: [x_pos + width, y_pos + height]
+                    }
+				}
+				UIAnchor::RightAligned((x, y)) => {
+					let x_pos = ui_internals.window_size.0 as f32 - x - width;
+					let y_pos = y + i as f32 * (height + BUFFER_DISTANCE);
+                    glyph_brush::Rectangle {
+                        min: [x_pos, y_pos],
+                        max: [x_pos + width, y_pos + height]
                     }
 				}
             };
@@ -688,7 +720,7 @@ impl<'a> Menu<'a> {
 		    );
 
 		    //Finally insert the section into the array
-		    let section_id = ui_internals.sections.insert(section);
+		    let section_id = ui_internals.sections.insert(section.to_owned());
 
     		let button = UIButton::new(section_id, button_bounds, self.button_commands[i]);
     		self.ids[i] = ui_internals.add_button(button);
@@ -696,6 +728,15 @@ impl<'a> Menu<'a> {
         self.active = true;
     }
 
+    //Replaces a button's label, rebuilding the menu if it's on screen
+    pub fn set_label(&mut self, index: usize, label: String, ui_internals: &mut UIInternals<'a>) {
+        self.button_labels[index] = Cow::Owned(label);
+        if self.active {
+            self.hide(ui_internals);
+            self.show(ui_internals);
+        }
+    }
+
     //Remove this menu's data from the arrays of buttons and sections
     pub fn hide(&mut self, ui_internals: &mut UIInternals<'a>) {
         if !self.active { return; }
@@ -718,7 +759,20 @@ impl<'a> Menu<'a> {
 pub enum UIAnchor {
     LeftAligned((f32, f32)),			//Parameter is the screen-space position of the top-left corner of the entire menu's bounding box
 	DeadCenter,
-	CenterTop(f32)						//Parameter is the offset from the top in pixels
+	CenterTop(f32),						//Parameter is the offset from the top in pixels
+	RightAligned((f32, f32))			//Parameter is the offset of the top-right corner of the bounding box from the top-right corner of the window
+}
+
+impl UIAnchor {
+	//Screen-space position of the top-left corner of a box of the given size
+	pub fn place(&self, size: (f32, f32), window_size: (u32, u32)) -> (f32, f32) {
+		match self {
+			UIAnchor::LeftAligned(pos) => { *pos }
+			UIAnchor::DeadCenter => { ((window_size.0 as f32 - size.0) / 2.0, (window_size.1 as f32 - size.1) / 2.0) }
+			UIAnchor::CenterTop(offset) => { ((window_size.0 as f32 - size.0) / 2.0, *offset) }
+			UIAnchor::RightAligned((x, y)) => { (window_size.0 as f32 - x - size.0, *y) }
+		}
+	}
 }
 
 #[derive(PartialEq, Eq, Debug)]
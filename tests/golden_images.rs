@@ -0,0 +1,35 @@
+//Renders each golden scene in a hidden window and compares it with the stored image in goldens/
+//These need an OpenGL 4.3 context so they're ignored by default
+//On a machine without a GPU, run them on Mesa's software rasterizer:
+//    LIBGL_ALWAYS_SOFTWARE=1 xvfb-run cargo test -- --ignored
+//After an intended visual change, rerun the game with --golden <scene> --update-goldens and commit the new images
+//Goldens have to come from the same software rasterizer the tests run on, since GPUs differ by more than the comparison tolerates
+use std::path::Path;
+use std::process::Command;
+
+fn check_scene(scene: &str) {
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("goldens").join(format!("{}.png", scene));
+    assert!(golden.is_file(),
+        "{} hasn't been generated yet, create it with\n    LIBGL_ALWAYS_SOFTWARE=1 xvfb-run cargo run -- --golden {} --update-goldens\nthen check the image and commit it",
+        golden.display(), scene);
+
+    let status = Command::new(env!("CARGO_BIN_EXE_whee_tanks"))
+        .args(&["--golden", scene])
+        .args(&["--golden-dir", concat!(env!("CARGO_MANIFEST_DIR"), "/goldens")])
+        .args(&["--asset-root", env!("CARGO_MANIFEST_DIR")])
+        .status()
+        .expect("couldn't launch the game");
+    assert!(status.success(), "\"{}\" doesn't match its golden image, see goldens/{}.diff.png", scene, scene);
+}
+
+#[test]
+#[ignore]
+fn arena() { check_scene("arena"); }
+
+#[test]
+#[ignore]
+fn duel() { check_scene("duel"); }
+
+#[test]
+#[ignore]
+fn paused() { check_scene("paused"); }